@@ -0,0 +1,169 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// How long responses are used when no TTL is given
+pub const DEFAULT_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Responses of version sources, kept on disk so that repeated updates don't fetch them again
+#[derive(Debug, Clone)]
+pub struct Cache {
+    /// `$XDG_CACHE_HOME/tuxwrangler`, the cache is disabled when there is no cache directory
+    dir: Option<PathBuf>,
+    ttl: Duration,
+    /// Ignore cached responses, new responses are still cached
+    refresh: bool,
+    /// Use cached responses regardless of their age and never fetch new ones
+    offline: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    key: String,
+    /// Seconds since the epoch
+    created: u64,
+    value: Value,
+}
+
+/// A cached response
+#[derive(Debug)]
+pub struct CacheEntry {
+    pub source: String,
+    pub key: String,
+    pub age: Duration,
+    pub expired: bool,
+}
+
+impl Cache {
+    pub fn new(ttl: Duration, refresh: bool, offline: bool) -> Self {
+        Self {
+            dir: env::var_os("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+                .map(|cache| cache.join("tuxwrangler")),
+            ttl,
+            refresh: refresh && !offline,
+            offline,
+        }
+    }
+
+    pub(crate) fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Fail instead of fetching a response that isn't cached while offline
+    pub(crate) fn check_online(&self, source: &str, key: &str) -> Result<()> {
+        if self.offline {
+            return Err(anyhow!(
+                "No cached {source} response for '{key}', it can't be fetched offline"
+            ));
+        }
+        Ok(())
+    }
+
+    /// The cached response for a key of a source, if it is younger than the TTL or offline
+    pub(crate) fn get<T: DeserializeOwned>(&self, source: &str, key: &str) -> Option<T> {
+        if self.refresh {
+            return None;
+        }
+        let stored = read(&self.path(source, key)?).ok()?;
+        if stored.key != key || (age(stored.created) > self.ttl && !self.offline) {
+            return None;
+        }
+        debug!("Using cached {source} response for '{key}'");
+        serde_json::from_value(stored.value).ok()
+    }
+
+    /// Cache a response, failing to write the cache only skips it
+    pub(crate) fn set<T: Serialize>(&self, source: &str, key: &str, value: &T) {
+        let Some(path) = self.path(source, key) else {
+            return;
+        };
+        if let Err(e) = write(&path, key, value) {
+            debug!("Unable to cache {source} response for '{key}': {e:?}");
+        }
+    }
+
+    /// Every cached response, sorted by source and key
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let Some(dir) = self.dir.as_ref().filter(|dir| dir.exists()) else {
+            return Ok(Vec::new());
+        };
+        let mut entries = Vec::new();
+        for source in fs::read_dir(dir)? {
+            let source = source?;
+            if !source.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(source.path())? {
+                let path = file?.path();
+                if path.extension().is_none_or(|extension| extension != "json") {
+                    continue;
+                }
+                if let Ok(stored) = read(&path) {
+                    let age = age(stored.created);
+                    entries.push(CacheEntry {
+                        source: source.file_name().to_string_lossy().to_string(),
+                        key: stored.key,
+                        age,
+                        expired: age > self.ttl,
+                    });
+                }
+            }
+        }
+        entries.sort_by(|a, b| (&a.source, &a.key).cmp(&(&b.source, &b.key)));
+        Ok(entries)
+    }
+
+    /// Remove every cached response, returning how many there were
+    pub fn clear(&self) -> Result<usize> {
+        let count = self.entries()?.len();
+        if let Some(dir) = self.dir.as_ref().filter(|dir| dir.exists()) {
+            fs::remove_dir_all(dir).context(format!("Unable to remove '{}'", dir.display()))?;
+        }
+        Ok(count)
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    fn path(&self, source: &str, key: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| {
+            dir.join(source)
+                .join(format!("{:x}.json", Sha256::digest(key.as_bytes())))
+        })
+    }
+}
+
+fn read(path: &Path) -> Result<Stored> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+fn write<T: Serialize>(path: &Path, key: &str, value: &T) -> Result<()> {
+    let stored = Stored {
+        key: key.to_string(),
+        created: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        value: serde_json::to_value(value)?,
+    };
+    fs::create_dir_all(path.parent().context("The cache path has a parent")?)?;
+    // Write to a temporary file first so that parallel runs never read a partial response
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temporary, serde_json::to_vec(&stored)?)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+fn age(created: u64) -> Duration {
+    (UNIX_EPOCH + Duration::from_secs(created))
+        .elapsed()
+        .unwrap_or_default()
+}
@@ -0,0 +1,181 @@
+use itertools::Itertools;
+
+use crate::diff::{Diff, LockChanges};
+
+/// A markdown table with a header row
+struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(header: Vec<&'static str>) -> Self {
+        Self {
+            header,
+            rows: Vec::new(),
+        }
+    }
+
+    fn render(&self) -> String {
+        vec![
+            row(self.header.iter()),
+            row(self.header.iter().map(|_| "---")),
+        ]
+        .into_iter()
+        .chain(self.rows.iter().map(|r| row(r.iter())))
+        .join("\n")
+    }
+}
+
+/// Render a single markdown table row
+fn row<T: AsRef<str>>(cells: impl Iterator<Item = T>) -> String {
+    format!(
+        "| {} |",
+        cells.map(|c| c.as_ref().replace('|', "\\|")).join(" | ")
+    )
+}
+
+/// Format a value as inline code, using `-` for missing values
+fn code(value: Option<&String>) -> String {
+    value
+        .map(|v| format!("`{v}`"))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+impl LockChanges {
+    /// Render the changes as a markdown summary, suitable for a pull request body
+    pub fn changelog(&self) -> String {
+        let mut sections = vec!["## Lock file changes".to_string()];
+        if !self.has_changes() {
+            sections.push("No changes.".to_string());
+            return sections.join("\n\n") + "\n";
+        }
+        if let Diff::Changed(from, to) = &self.registry {
+            sections.push(format!("Registry changed from `{from}` to `{to}`."));
+        }
+
+        let mut versions = Table::new(vec!["Type", "Name", "From", "To"]);
+        versions.rows.extend(
+            self.bases
+                .iter()
+                .map(|b| ("base", &b.base))
+                .chain(self.features.iter().map(|f| ("feature", &f.feature)))
+                .filter(|(_, diff)| !diff.is_same())
+                .map(|(kind, diff)| {
+                    let name = diff.to().or(diff.from()).map(|v| v.name.clone());
+                    vec![
+                        kind.to_string(),
+                        name.unwrap_or_default(),
+                        code(diff.from().map(|v| &v.version)),
+                        code(diff.to().map(|v| &v.version)),
+                    ]
+                }),
+        );
+
+        let mut digests = Table::new(vec!["Base", "Version", "Platform", "From", "To"]);
+        // Digests of added and removed bases are not refreshes
+        let kept_bases = self.bases.iter().filter(|b| b.base.from().is_some());
+        digests.rows.extend(kept_bases.flat_map(|b| {
+            let base = b.base.to();
+            b.identifier
+                .iter()
+                .map(|diff| (None, diff))
+                .chain(
+                    b.platforms
+                        .iter()
+                        .map(|(platform, diff)| (Some(platform), diff)),
+                )
+                .filter(|(_, diff)| !diff.is_same())
+                .filter_map(move |(platform, diff)| {
+                    let base = base?;
+                    Some(vec![
+                        base.name.clone(),
+                        code(Some(&base.version)),
+                        code(platform),
+                        code(diff.from()),
+                        code(diff.to()),
+                    ])
+                })
+        }));
+
+        let image = |name: Option<&String>, tag: Option<&String>| {
+            code(Some(&format!(
+                "{}:{}",
+                name.cloned().unwrap_or_default(),
+                tag.cloned().unwrap_or_default()
+            )))
+        };
+        let mut added = Table::new(vec!["Image", "Target"]);
+        let mut removed = Table::new(vec!["Image", "Target"]);
+        let refreshed_bases = self
+            .bases
+            .iter()
+            .filter(|b| b.is_refreshed())
+            .filter_map(|b| b.base.to())
+            .collect::<Vec<_>>();
+        let changed_steps = self
+            .features
+            .iter()
+            .filter(|f| !f.steps.is_empty())
+            .filter_map(|f| f.feature.to())
+            .collect::<Vec<_>>();
+        let mut affected = Table::new(vec!["Target", "Image", "Changes"]);
+        for build in &self.build {
+            let refreshed = build
+                .components
+                .iter()
+                .any(|c| refreshed_bases.contains(&c));
+            let steps = build.components.iter().any(|c| changed_steps.contains(&c));
+            if build.is_same() && !refreshed && !steps {
+                continue;
+            }
+            match &build.build {
+                Diff::Added(_) => added.rows.push(vec![
+                    image(build.image_name.to(), build.image_tag.to()),
+                    code(build.target.to()),
+                ]),
+                Diff::Removed(_) => removed.rows.push(vec![
+                    image(build.image_name.from(), build.image_tag.from()),
+                    code(build.target.from()),
+                ]),
+                _ => affected.rows.push(vec![
+                    code(build.target.to()),
+                    image(build.image_name.to(), build.image_tag.to()),
+                    [
+                        ("versions", &build.build),
+                        ("target", &build.target),
+                        ("image name", &build.image_name),
+                        ("image tag", &build.image_tag),
+                    ]
+                    .into_iter()
+                    .filter(|(_, diff)| !diff.is_same())
+                    .map(|(name, _)| name)
+                    .chain(
+                        build
+                            .tags
+                            .iter()
+                            .any(|diff| !diff.is_same())
+                            .then_some("alias tags"),
+                    )
+                    .chain(refreshed.then_some("base digest"))
+                    .chain(steps.then_some("steps"))
+                    .join(", "),
+                ]),
+            }
+        }
+
+        for (title, table) in [
+            ("Version changes", versions),
+            ("Digest refreshes", digests),
+            ("New images", added),
+            ("Removed images", removed),
+            ("Affected builds", affected),
+        ] {
+            if !table.rows.is_empty() {
+                sections.push(format!("### {title}\n\n{}", table.render()));
+            }
+        }
+
+        sections.join("\n\n") + "\n"
+    }
+}
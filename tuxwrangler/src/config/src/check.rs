@@ -0,0 +1,144 @@
+use std::collections::BTreeSet;
+
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    diff::EntryKey, lock::SingleVersioned, Result, TuxWranglerConfig, TuxWranglerConfigLocked,
+    TOOL_VERSION,
+};
+
+impl TuxWranglerConfig {
+    /// A hash of the normalized config.
+    /// The config is normalized through `serde_json::Value`, which keeps map keys sorted.
+    pub(crate) fn hash(&self) -> Result<String> {
+        let normalized = serde_json::to_value(self)?.to_string();
+        Ok(format!(
+            "sha256:{:x}",
+            Sha256::digest(normalized.as_bytes())
+        ))
+    }
+
+    /// Every reason the lock file is out of date with this config.
+    /// This does not make any network calls, so versions are only compared by their target version.
+    pub(crate) fn stale_reasons(&self, lock: &TuxWranglerConfigLocked) -> Result<Vec<String>> {
+        let mut reasons = Vec::new();
+        match &lock.config_hash {
+            None => reasons.push("The lock file does not contain a config hash".to_string()),
+            Some(hash) if hash != &self.hash()? => {
+                reasons.push("The config has changed since the lock file was created".to_string())
+            }
+            _ => {}
+        }
+        match &lock.tool_version {
+            Some(version) if version == TOOL_VERSION => {}
+            version => reasons.push(format!(
+                "The lock file was created by tuxwrangler '{}' instead of '{TOOL_VERSION}'",
+                version.as_deref().unwrap_or("unknown")
+            )),
+        }
+        if self.registry != lock.registry {
+            reasons.push(format!(
+                "The registry '{}' does not match the locked registry '{}'",
+                self.registry, lock.registry
+            ));
+        }
+
+        let key = |v: &SingleVersioned| (v.name.clone(), v.version.clone());
+        let bases: BTreeSet<EntryKey> = self
+            .bases
+            .iter()
+            .flat_map(|base| {
+                base.definition
+                    .versioned
+                    .versions
+                    .iter()
+                    .map(|version| (base.definition.versioned.name.clone(), version.clone()))
+            })
+            .collect();
+        let features: BTreeSet<EntryKey> = self
+            .features
+            .iter()
+            .flat_map(|feature| {
+                feature
+                    .definition
+                    .versioned
+                    .versions
+                    .iter()
+                    .map(|version| (feature.definition.versioned.name.clone(), version.clone()))
+            })
+            .collect();
+        let builds: BTreeSet<Vec<EntryKey>> = self
+            .build_combinations(&|name, target| {
+                lock.bases
+                    .iter()
+                    .map(|base| (&base.name, &base.target_version, &base.version))
+                    .chain(
+                        lock.features
+                            .iter()
+                            .map(|f| (&f.name, &f.target_version, &f.version)),
+                    )
+                    .find(|(n, t, _)| *n == name && t.as_deref() == Some(target))
+                    .map(|(_, _, version)| version.clone())
+            })
+            .into_iter()
+            .map(|(_, base, features)| {
+                vec![key(&base)]
+                    .into_iter()
+                    .chain(features.iter().map(key))
+                    .collect()
+            })
+            .collect();
+        reasons.extend(mismatches(
+            "base",
+            &bases,
+            &lock.bases.iter().map(|base| base.key()).collect(),
+            |(name, version)| format!("{name}:{version}"),
+        ));
+        reasons.extend(mismatches(
+            "feature",
+            &features,
+            &lock.features.iter().map(|feature| feature.key()).collect(),
+            |(name, version)| format!("{name}:{version}"),
+        ));
+        reasons.extend(mismatches(
+            "build",
+            &builds,
+            &lock
+                .builds
+                .iter()
+                .map(|build| lock.build_key(build))
+                .collect(),
+            |keys| {
+                keys.iter()
+                    .map(|(name, version)| format!("{name}:{version}"))
+                    .join(" ")
+            },
+        ));
+        Ok(reasons)
+    }
+}
+
+/// Describe the entries expected from the config that are missing from the lock file and the other way around
+fn mismatches<T: Ord>(
+    kind: &str,
+    expected: &BTreeSet<T>,
+    locked: &BTreeSet<T>,
+    describe: impl Fn(&T) -> String,
+) -> Vec<String> {
+    expected
+        .difference(locked)
+        .map(|entry| {
+            format!(
+                "The {kind} '{}' is missing from the lock file",
+                describe(entry)
+            )
+        })
+        .chain(locked.difference(expected).map(|entry| {
+            format!(
+                "The {kind} '{}' in the lock file is not in the config",
+                describe(entry)
+            )
+        }))
+        .collect()
+}
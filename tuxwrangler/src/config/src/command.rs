@@ -0,0 +1,69 @@
+use std::{env, process::Stdio, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use regex::Regex;
+use tokio::process::Command;
+
+use crate::config::CommandFetchVersion;
+
+/// Environment variables every command receives
+const BASE_ENV: [&str; 2] = ["PATH", "HOME"];
+
+impl CommandFetchVersion {
+    /// Run the command on the host with the given (already templated) arguments
+    /// and read the version from its output
+    pub(crate) async fn version(&self, args: &[String]) -> Result<String> {
+        let description = format!("{} {}", self.command, args.join(" "));
+        info!("Fetching version from command '{description}'");
+        let child = Command::new(&self.command)
+            .args(args)
+            .env_clear()
+            .envs(
+                BASE_ENV
+                    .iter()
+                    .map(|name| name.to_string())
+                    .chain(self.env.iter().cloned())
+                    .filter_map(|name| env::var(&name).ok().map(|value| (name, value))),
+            )
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context(format!("Unable to run '{description}'"))?;
+        let output =
+            tokio::time::timeout(Duration::from_secs(self.timeout), child.wait_with_output())
+                .await
+                .map_err(|_| {
+                    anyhow!(
+                        "'{description}' did not finish within {} seconds",
+                        self.timeout
+                    )
+                })??;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() {
+            return Err(anyhow!(
+                "'{description}' failed with {}:\n{}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        debug!("'{description}' returned '{stdout}'");
+        match &self.regex {
+            Some(regex) => {
+                let re = Regex::new(regex).context(format!("Invalid regex '{regex}'"))?;
+                re.captures(&stdout)
+                    .and_then(|c| c.name("version").or_else(|| c.get(1)).or_else(|| c.get(0)))
+                    .map(|m| m.as_str().to_string())
+                    .context(format!("No output of '{description}' matched '{regex}'"))
+            }
+            None => stdout
+                .lines()
+                .map(str::trim)
+                .rfind(|line| !line.is_empty())
+                .map(|line| line.to_string())
+                .context(format!("No output from '{description}'")),
+        }
+    }
+}
@@ -1,6 +1,8 @@
+use std::collections::{BTreeMap, HashMap};
+
 use serde::{Deserialize, Serialize};
 
-use crate::lock::Layer;
+use crate::lock::{BuildArg, Layer, Secret};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TuxWranglerConfig {
@@ -18,6 +20,14 @@ pub struct TuxWranglerConfig {
     /// The abstract builds that should be run for this configuration
     #[serde(rename = "build", default)]
     pub(crate) builds: Vec<Build>,
+
+    /// Build arguments feature steps can declare, read from the environment, a file or a literal
+    #[serde(rename = "build-args", default)]
+    pub(crate) build_args: BTreeMap<String, BuildArg>,
+
+    /// BuildKit secrets feature steps can mount, read from the environment or a file
+    #[serde(default)]
+    pub(crate) secrets: BTreeMap<String, Secret>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,6 +59,9 @@ pub struct BaseDefinition {
     pub(crate) definition: VersionedDefinition,
     pub(crate) image: String,
     pub(crate) package_manager: String,
+    /// The platforms (`linux/amd64`, `linux/arm64`, ...) the base is locked and built for
+    #[serde(default)]
+    pub(crate) platforms: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -75,6 +88,10 @@ pub struct VersionedDefinition {
 pub enum FetchVersion {
     Docker(DockerFetchVersion),
     Github(GithubFetchVersion),
+    Registry(RegistryFetchVersion),
+    Http(HttpFetchVersion),
+    Command(CommandFetchVersion),
+    Git(GitFetchVersion),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -82,15 +99,124 @@ pub enum FetchVersion {
 pub struct DockerFetchVersion {
     pub(crate) image: String,
     pub(crate) command: Vec<String>,
+    #[serde(flatten)]
+    pub(crate) filter: TagFilter,
+}
+
+/// Match the tags of an image in an OCI distribution registry
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RegistryFetchVersion {
+    /// The image whose repository is listed, e.g. `eclipse-temurin` or `ghcr.io/org/app`
+    pub(crate) image: String,
+    /// How tags are ordered when picking the newest match
+    #[serde(default)]
+    pub(crate) order: VersionOrder,
+    #[serde(flatten)]
+    pub(crate) filter: TagFilter,
+}
+
+/// Extract versions from a JSON document
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HttpFetchVersion {
+    /// The URL of the document (templated with `{{version}}`)
+    pub(crate) url: String,
+    /// A JSONPath selecting the candidate versions, e.g. `$.releases[*].version`
+    pub(crate) json_path: String,
+    /// Headers sent with the request
+    #[serde(default)]
+    pub(crate) headers: HashMap<String, String>,
+    /// How versions are ordered when picking the newest match
+    #[serde(default)]
+    pub(crate) order: VersionOrder,
+    #[serde(flatten)]
+    pub(crate) filter: TagFilter,
+}
+
+/// Run a command on the host that prints the version
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CommandFetchVersion {
+    /// The program that is run
+    pub(crate) command: String,
+    /// The arguments passed to the program (templated with `{{version}}`)
+    #[serde(default)]
+    pub(crate) args: Vec<String>,
+    /// A regex whose `version` group (or first group) is the version, the last line of output is used otherwise
+    pub(crate) regex: Option<String>,
+    /// Seconds to wait for the command to finish
+    #[serde(default = "default_command_timeout")]
+    pub(crate) timeout: u64,
+    /// Environment variables passed through to the command, along with `PATH` and `HOME`
+    #[serde(default)]
+    pub(crate) env: Vec<String>,
+    #[serde(flatten)]
+    pub(crate) filter: TagFilter,
+}
+
+fn default_command_timeout() -> u64 {
+    60
+}
+
+/// Match the tags or branches of any git repository
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GitFetchVersion {
+    /// The repository URL (templated with `{{version}}`)
+    pub(crate) url: String,
+    #[serde(default)]
+    pub(crate) version_from: VersionFrom,
+    /// How refs are ordered when picking the newest match
+    #[serde(default)]
+    pub(crate) order: VersionOrder,
+    #[serde(flatten)]
+    pub(crate) filter: TagFilter,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct GithubFetchVersion {
+    /// The API URL of a GitHub Enterprise Server, e.g. `https://github.example.com/api/v3`
+    pub(crate) host: Option<String>,
     pub(crate) org: String,
     pub(crate) project: String,
     #[serde(default)]
     pub(crate) version_from: VersionFrom,
+    /// How tags are ordered when picking the newest match
+    #[serde(default)]
+    pub(crate) order: VersionOrder,
+    /// Also use draft releases when `version-from = "release"`
+    #[serde(default)]
+    pub(crate) include_drafts: bool,
+    /// Also use releases marked as pre-releases when `version-from = "release"`
+    #[serde(default)]
+    pub(crate) include_prereleases: bool,
+    #[serde(flatten)]
+    pub(crate) filter: TagFilter,
+}
+
+/// Rules for which fetched tags can be used as a version and how they are normalized
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TagFilter {
+    /// Only use tags matching at least one of these regexes
+    #[serde(default)]
+    pub(crate) include: Vec<String>,
+    /// Never use tags matching any of these regexes
+    #[serde(default)]
+    pub(crate) exclude: Vec<String>,
+    /// Whether pre-releases (`-rc1`, `beta`, `nightly`, ...) can be used
+    #[serde(default = "default_prerelease")]
+    pub(crate) prerelease: bool,
+    /// A prefix removed from tags, e.g. `v` for `v1.2.3`
+    pub(crate) strip_prefix: Option<String>,
+    /// A regex whose `version` group (or first group) is used as the version, tags that don't match are skipped
+    pub(crate) capture: Option<String>,
+}
+
+fn default_prerelease() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -99,6 +225,8 @@ pub(crate) enum VersionFrom {
     #[default]
     Tag,
     Branch,
+    /// Published GitHub releases
+    Release,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -108,4 +236,28 @@ pub struct Build {
     pub(crate) features: Vec<Vec<BuildDefinition>>,
     pub(crate) image_name: String,
     pub(crate) image_tag: String,
+    /// Additional tags the image should receive (templated like `image-tag`)
+    #[serde(default)]
+    pub(crate) alias_tags: Vec<String>,
+    /// The platforms to build, defaults to the platforms of the base
+    #[serde(default)]
+    pub(crate) platforms: Vec<String>,
+    /// Labels added to each image (values are templated like `image-tag`)
+    #[serde(default)]
+    pub(crate) labels: BTreeMap<String, String>,
+}
+
+/// The ordering used to pick the newest of several matching versions
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum VersionOrder {
+    /// Compare each segment of the version, numerically when both segments are numbers
+    #[default]
+    Numeric,
+    /// Semantic versioning, where pre-releases are older than their release
+    Semver,
+    /// Compare the date (`YYYY-MM-DD`, `YYYYMMDD`, ...) contained in the version
+    Date,
+    /// Keep the order the versions were returned in
+    Api,
 }
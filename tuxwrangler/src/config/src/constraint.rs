@@ -0,0 +1,212 @@
+use std::cmp::Ordering;
+
+use anyhow::{anyhow, Result};
+
+use crate::version::{compare_numeric, split_version, version_match};
+
+const OPERATORS: [&str; 8] = [">=", "<=", "!=", ">", "<", "=", "~", "^"];
+
+/// A version requirement such as `>=17, <18, !=17.0.5`.
+/// Every comparator has to match. Partial versions cover every version they are a prefix of,
+/// so `<=17` matches `17.0.5` while `>17` does not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Constraint {
+    comparators: Vec<Comparator>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparator {
+    operator: Operator,
+    version: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    /// `=` or no operator, the version is a prefix of the source
+    Exact,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    /// `~`, updates that keep the major and minor version
+    Tilde,
+    /// `^`, updates that don't change the leftmost non-zero segment
+    Caret,
+}
+
+/// Whether a target version is a constraint rather than a plain version or wildcard
+pub(crate) fn is_constraint(target: &str) -> bool {
+    target.contains(',') || OPERATORS.iter().any(|op| target.trim().starts_with(op))
+}
+
+impl Constraint {
+    pub(crate) fn parse(target: &str) -> Result<Self> {
+        let comparators = target
+            .split(',')
+            .map(|comparator| {
+                let comparator = comparator.trim();
+                let (operator, version) = OPERATORS
+                    .iter()
+                    .find_map(|op| comparator.strip_prefix(op).map(|v| (*op, v)))
+                    .unwrap_or(("=", comparator));
+                let version = version.trim();
+                if version.is_empty() {
+                    return Err(anyhow!(
+                        "Missing version after '{operator}' in constraint '{target}'"
+                    ));
+                }
+                Ok(Comparator {
+                    operator: match operator {
+                        ">=" => Operator::GreaterEqual,
+                        "<=" => Operator::LessEqual,
+                        "!=" => Operator::NotEqual,
+                        ">" => Operator::Greater,
+                        "<" => Operator::Less,
+                        "~" => Operator::Tilde,
+                        "^" => Operator::Caret,
+                        _ => Operator::Exact,
+                    },
+                    version: version.to_string(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { comparators })
+    }
+
+    pub(crate) fn matches(&self, source: &str) -> bool {
+        self.comparators.iter().all(|c| c.matches(source))
+    }
+
+    /// The version used for `{{version}}` when a template is rendered with this constraint
+    pub(crate) fn template_version(&self) -> &str {
+        self.comparators
+            .first()
+            .map(|c| c.version.as_str())
+            .unwrap_or_default()
+    }
+}
+
+impl Comparator {
+    fn matches(&self, source: &str) -> bool {
+        let ordering = compare_numeric(source, &self.version);
+        let prefix = version_match(&self.version, source);
+        match self.operator {
+            Operator::Exact => prefix,
+            Operator::NotEqual => !prefix,
+            Operator::Greater => ordering == Ordering::Greater && !prefix,
+            Operator::GreaterEqual => ordering != Ordering::Less || prefix,
+            Operator::Less => ordering == Ordering::Less && !prefix,
+            Operator::LessEqual => ordering != Ordering::Greater || prefix,
+            Operator::Tilde => {
+                let segments = self.segments();
+                let fixed = segments.len().min(2);
+                (ordering != Ordering::Less || prefix)
+                    && version_match(&segments[..fixed].join("."), source)
+            }
+            Operator::Caret => {
+                let segments = self.segments();
+                let fixed = segments
+                    .iter()
+                    .position(|segment| segment != "0")
+                    .map(|i| i + 1)
+                    .unwrap_or(segments.len());
+                (ordering != Ordering::Less || prefix)
+                    && version_match(&segments[..fixed].join("."), source)
+            }
+        }
+    }
+
+    fn segments(&self) -> Vec<String> {
+        split_version(&self.version)
+            .into_iter()
+            .filter(|segment| !segment.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(constraint: &str, version: &str) -> bool {
+        Constraint::parse(constraint).unwrap().matches(version)
+    }
+
+    #[test]
+    fn detects_constraints() {
+        assert!(is_constraint(">=17"));
+        assert!(is_constraint("17, !=17.0.5"));
+        assert!(is_constraint(" ~1.2"));
+        assert!(!is_constraint("17"));
+        assert!(!is_constraint("17.*"));
+        assert!(!is_constraint("latest"));
+    }
+
+    #[test]
+    fn parses_every_operator() {
+        let constraint = Constraint::parse(">=1, <=2, !=3, >4, <5, =6, ~7, ^8, 9").unwrap();
+        let operators = constraint
+            .comparators
+            .iter()
+            .map(|c| (c.operator, c.version.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            operators,
+            [
+                (Operator::GreaterEqual, "1"),
+                (Operator::LessEqual, "2"),
+                (Operator::NotEqual, "3"),
+                (Operator::Greater, "4"),
+                (Operator::Less, "5"),
+                (Operator::Exact, "6"),
+                (Operator::Tilde, "7"),
+                (Operator::Caret, "8"),
+                (Operator::Exact, "9"),
+            ]
+        );
+        assert_eq!(constraint.template_version(), "1");
+    }
+
+    #[test]
+    fn rejects_missing_versions() {
+        assert!(Constraint::parse(">=").is_err());
+        assert!(Constraint::parse(">=17,").is_err());
+    }
+
+    #[test]
+    fn bare_major_versions_cover_their_releases() {
+        assert!(matches("17", "17.0.5"));
+        assert!(!matches("17", "18.0.1"));
+        assert!(matches("<=17", "17.0.5"));
+        assert!(!matches(">17", "17.0.5"));
+        assert!(matches(">17", "18"));
+        assert!(matches(">=17", "17.0.1"));
+        assert!(!matches("<17", "17.0.1"));
+        assert!(matches("<17", "16.9"));
+    }
+
+    #[test]
+    fn not_equal_excludes_versions() {
+        assert!(matches(">=17, <18, !=17.0.5", "17.0.4"));
+        assert!(!matches(">=17, <18, !=17.0.5", "17.0.5"));
+        assert!(!matches(">=17, <18, !=17.0.5", "18.0.0"));
+        assert!(!matches("!=17", "17.0.4"));
+    }
+
+    #[test]
+    fn tilde_keeps_major_and_minor() {
+        assert!(matches("~1.2.3", "1.2.9"));
+        assert!(!matches("~1.2.3", "1.2.2"));
+        assert!(!matches("~1.2.3", "1.3.0"));
+        assert!(matches("~1", "1.9"));
+    }
+
+    #[test]
+    fn caret_keeps_leftmost_non_zero_segment() {
+        assert!(matches("^1.2.3", "1.9.0"));
+        assert!(!matches("^1.2.3", "2.0.0"));
+        assert!(matches("^0.2.3", "0.2.9"));
+        assert!(!matches("^0.2.3", "0.3.0"));
+    }
+}
@@ -1,11 +1,19 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use itertools::Itertools;
+use serde::Serialize;
 
 use crate::{
-    config::Build,
-    lock::{InstallationConfig, BaseConfig, SingleVersioned},
+    lock::{BaseConfig, Installation, InstallationConfig, Layer, SingleBuild, SingleVersioned},
     TuxWranglerConfigLocked,
 };
 
+/// A name and target version from the config, used to match entries between 2 lock files
+pub(crate) type EntryKey = (String, String);
+
 /// A representation of the changes bewtween 2 configurations
 pub struct Changes {
     title: String,
@@ -13,17 +21,46 @@ pub struct Changes {
     inner: Vec<Changes>,
 }
 
+#[derive(Serialize)]
 pub struct LockChanges {
-    registry: Diff<String>,
-    bases: Vec<Diff<SingleVersioned>>,
-    features: Vec<Diff<SingleVersioned>>,
-    build: Vec<BuildDiff>,
+    pub registry: Diff<String>,
+    pub bases: Vec<BaseDiff>,
+    pub features: Vec<FeatureDiff>,
+    pub build: Vec<BuildDiff>,
+}
+
+/// The changes for a single base, matched by name and target version
+#[derive(Serialize)]
+pub struct BaseDiff {
+    pub base: Diff<SingleVersioned>,
+    pub identifier: Option<Diff<String>>,
+    pub tag: Option<Diff<String>>,
+    /// The digest of each platform of the base
+    pub platforms: BTreeMap<String, Diff<String>>,
+}
+
+/// The changes for a single feature, matched by name and target version
+#[derive(Serialize)]
+pub struct FeatureDiff {
+    pub feature: Diff<SingleVersioned>,
+    pub steps: Vec<Diff<String>>,
+    pub tag: Option<Diff<String>>,
 }
 
+/// The changes for a single build, matched by its image name and the target versions of its base and features
+#[derive(Serialize)]
 pub struct BuildDiff {
-    tags: Vec<Diff<String>>,
+    pub build: Diff<String>,
+    pub target: Diff<String>,
+    pub image_name: Diff<String>,
+    pub image_tag: Diff<String>,
+    pub tags: Vec<Diff<String>>,
+    /// The base and features of the build, from the newest lock file that contains it
+    pub components: Vec<SingleVersioned>,
 }
 
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "kebab-case")]
 pub enum Diff<T> {
     Same(T),
     Added(T),
@@ -32,7 +69,7 @@ pub enum Diff<T> {
 }
 
 impl<T: Eq> Diff<T> {
-    fn diff(from: T, to: T) -> Diff<T> {
+    fn compare(from: T, to: T) -> Diff<T> {
         if from == to {
             Self::Same(from)
         } else {
@@ -45,83 +82,609 @@ impl<T: Eq> Diff<T> {
             (None, None) => None,
             (None, Some(t)) => Some(Self::Added(t)),
             (Some(t), None) => Some(Self::Removed(t)),
-            (Some(f), Some(t)) => Some(Self::diff(f, t)),
+            (Some(f), Some(t)) => Some(Self::compare(f, t)),
         }
     }
 }
 
+impl<T> Diff<T> {
+    pub fn is_same(&self) -> bool {
+        matches!(self, Diff::Same(_))
+    }
+
+    /// The value before the change, if there was one
+    pub fn from(&self) -> Option<&T> {
+        match self {
+            Diff::Same(t) | Diff::Removed(t) | Diff::Changed(t, _) => Some(t),
+            Diff::Added(_) => None,
+        }
+    }
+
+    /// The value after the change, if there is one
+    pub fn to(&self) -> Option<&T> {
+        match self {
+            Diff::Same(t) | Diff::Added(t) | Diff::Changed(_, t) => Some(t),
+            Diff::Removed(_) => None,
+        }
+    }
+
+    fn is_added_or_removed(&self) -> bool {
+        matches!(self, Diff::Added(_) | Diff::Removed(_))
+    }
+}
+
+impl<T: Display> Display for Diff<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diff::Same(t) => write!(f, "  {t}"),
+            Diff::Added(t) => write!(f, "+ {t}"),
+            Diff::Removed(t) => write!(f, "- {t}"),
+            Diff::Changed(from, to) => write!(f, "~ {from} -> {to}"),
+        }
+    }
+}
+
+impl BaseDiff {
+    pub fn is_same(&self) -> bool {
+        self.base.is_same()
+            && self.identifier.as_ref().is_none_or(Diff::is_same)
+            && self.tag.as_ref().is_none_or(Diff::is_same)
+            && self.platforms.values().all(Diff::is_same)
+    }
+
+    /// Whether the digest of the base or of any of its platforms changed
+    pub fn is_refreshed(&self) -> bool {
+        self.identifier.as_ref().is_some_and(|d| !d.is_same())
+            || self.platforms.values().any(|d| !d.is_same())
+    }
+}
+
+impl FeatureDiff {
+    pub fn is_same(&self) -> bool {
+        self.feature.is_same()
+            && self.steps.is_empty()
+            && self.tag.as_ref().is_none_or(Diff::is_same)
+    }
+}
+
+impl BuildDiff {
+    pub fn is_same(&self) -> bool {
+        self.build.is_same()
+            && self.target.is_same()
+            && self.image_name.is_same()
+            && self.image_tag.is_same()
+            && self.tags.iter().all(Diff::is_same)
+    }
+}
+
+impl LockChanges {
+    /// Whether anything differs between the 2 lock files
+    pub fn has_changes(&self) -> bool {
+        !(self.registry.is_same()
+            && self.bases.iter().all(BaseDiff::is_same)
+            && self.features.iter().all(FeatureDiff::is_same)
+            && self.build.iter().all(BuildDiff::is_same))
+    }
+}
+
 impl TuxWranglerConfigLocked {
     pub fn update_changes(self, next: Self) -> LockChanges {
         //base
-        let original: HashSet<SingleVersioned> = self
-            .bases
-            .into_iter()
-            .map(|pc| SingleVersioned {
-                name: pc.name,
-                version: pc.version,
+        let base_diffs = keyed_diffs(
+            self.bases.iter().map(|b| (b.key(), b)).collect(),
+            next.bases.iter().map(|b| (b.key(), b)).collect(),
+        )
+        .into_iter()
+        .filter_map(|(from, to)| {
+            Some(BaseDiff {
+                base: Diff::option_diff(
+                    from.map(BaseConfig::single_versioned),
+                    to.map(BaseConfig::single_versioned),
+                )?,
+                identifier: Diff::option_diff(
+                    from.map(|b| b.identifier.to_string()),
+                    to.map(|b| b.identifier.to_string()),
+                ),
+                tag: Diff::option_diff(
+                    from.and_then(|b| b.tag.clone()),
+                    to.and_then(|b| b.tag.clone()),
+                ),
+                platforms: from
+                    .into_iter()
+                    .chain(to)
+                    .flat_map(|b| b.platforms.keys())
+                    .unique()
+                    .filter_map(|platform| {
+                        let digest = |b: &BaseConfig| b.platforms.get(platform).cloned();
+                        Some((
+                            platform.clone(),
+                            Diff::option_diff(from.and_then(digest), to.and_then(digest))?,
+                        ))
+                    })
+                    .collect(),
             })
-            .collect();
-        let new: HashSet<SingleVersioned> = next
-            .bases
-            .into_iter()
-            .map(|pc| SingleVersioned {
-                name: pc.name,
-                version: pc.version,
-            })
-            .collect();
-
-        let base_diffs = new
-            .difference(&original)
-            // new bases
-            .map(|sv| Diff::Added(sv.clone()))
-            // removed bases
-            .chain(
-                original
-                    .difference(&new)
-                    .map(|sv| Diff::Removed(sv.clone())),
-            )
-            // unchanged bases
-            .chain(original.intersection(&new).map(|sv| Diff::Same(sv.clone())))
-            .collect();
+        })
+        .collect();
 
         //features
-        let original: HashSet<SingleVersioned> = self
-            .features
-            .into_iter()
-            .map(|pc| SingleVersioned {
-                name: pc.name,
-                version: pc.version,
+        let feature_diffs = keyed_diffs(
+            self.features.iter().map(|f| (f.key(), f)).collect(),
+            next.features.iter().map(|f| (f.key(), f)).collect(),
+        )
+        .into_iter()
+        .filter_map(|(from, to)| {
+            Some(FeatureDiff {
+                feature: Diff::option_diff(
+                    from.map(InstallationConfig::single_versioned),
+                    to.map(InstallationConfig::single_versioned),
+                )?,
+                steps: step_diffs(
+                    &from.map(InstallationConfig::step_lines).unwrap_or_default(),
+                    &to.map(InstallationConfig::step_lines).unwrap_or_default(),
+                ),
+                tag: Diff::option_diff(
+                    from.and_then(|f| f.tag.clone()),
+                    to.and_then(|f| f.tag.clone()),
+                ),
             })
-            .collect();
-        let new: HashSet<SingleVersioned> = next
-            .features
-            .into_iter()
-            .map(|pc| SingleVersioned {
-                name: pc.name,
-                version: pc.version,
-            })
-            .collect();
+        })
+        .collect();
 
-        let feature_diffs = new
-            .difference(&original)
-            // new features
-            .map(|sv| Diff::Added(sv.clone()))
-            // removed features
-            .chain(
-                original
-                    .difference(&new)
-                    .map(|sv| Diff::Removed(sv.clone())),
-            )
-            // unchanged features
-            .chain(original.intersection(&new).map(|sv| Diff::Same(sv.clone())))
-            .collect();
         //builds
+        let build_diffs = keyed_diffs(
+            self.builds
+                .iter()
+                .map(|b| ((b.image_name.clone(), self.build_key(b)), b))
+                .collect(),
+            next.builds
+                .iter()
+                .map(|b| ((b.image_name.clone(), next.build_key(b)), b))
+                .collect(),
+        )
+        .into_iter()
+        .filter_map(|(from, to)| {
+            let tags = |build: Option<&SingleBuild>| {
+                build
+                    .map(|b| b.alias_tags.iter().cloned().collect::<BTreeSet<_>>())
+                    .unwrap_or_default()
+            };
+            let (from_tags, to_tags) = (tags(from), tags(to));
+            Some(BuildDiff {
+                build: Diff::option_diff(from.map(|b| b.to_string()), to.map(|b| b.to_string()))?,
+                target: Diff::option_diff(
+                    from.map(|b| b.target.clone()),
+                    to.map(|b| b.target.clone()),
+                )?,
+                image_name: Diff::option_diff(
+                    from.map(|b| b.image_name.clone()),
+                    to.map(|b| b.image_name.clone()),
+                )?,
+                image_tag: Diff::option_diff(
+                    from.map(|b| b.image_tag.clone()),
+                    to.map(|b| b.image_tag.clone()),
+                )?,
+                tags: from_tags
+                    .union(&to_tags)
+                    .map(
+                        |tag| match (from_tags.contains(tag), to_tags.contains(tag)) {
+                            (true, true) => Diff::Same(tag.clone()),
+                            (true, false) => Diff::Removed(tag.clone()),
+                            _ => Diff::Added(tag.clone()),
+                        },
+                    )
+                    .collect(),
+                components: to
+                    .or(from)
+                    .map(|b| {
+                        vec![b.base.clone()]
+                            .into_iter()
+                            .chain(b.features.clone())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+        })
+        .collect();
 
         LockChanges {
-            registry: Diff::diff(self.registry, next.registry),
+            registry: Diff::compare(self.registry, next.registry),
             bases: base_diffs,
             features: feature_diffs,
-            build: Vec::new(),
+            build: build_diffs,
+        }
+    }
+
+    /// The key for a build, based on the target versions of its base and features
+    pub(crate) fn build_key(&self, build: &SingleBuild) -> Vec<EntryKey> {
+        let base_key = self
+            .base(&build.base)
+            .map(BaseConfig::key)
+            .unwrap_or_else(|| (build.base.name.clone(), build.base.version.clone()));
+        vec![base_key]
+            .into_iter()
+            .chain(build.features.iter().map(|feature| {
+                self.feature(feature)
+                    .map(InstallationConfig::key)
+                    .unwrap_or_else(|| (feature.name.clone(), feature.version.clone()))
+            }))
+            .collect()
+    }
+}
+
+/// Pair the entries of 2 maps by their keys
+fn keyed_diffs<K: Ord, V: Copy>(
+    from: BTreeMap<K, V>,
+    mut to: BTreeMap<K, V>,
+) -> Vec<(Option<V>, Option<V>)> {
+    let mut pairs: Vec<_> = from
+        .into_iter()
+        .map(|(key, from)| (Some(from), to.remove(&key)))
+        .collect();
+    pairs.extend(to.into_values().map(|to| (None, Some(to))));
+    pairs
+}
+
+/// Compute the lines that were removed from or added to a set of steps, preserving their order
+fn step_diffs(from: &[String], to: &[String]) -> Vec<Diff<String>> {
+    from.iter()
+        .filter(|line| !to.contains(line))
+        .map(|line| Diff::Removed(line.clone()))
+        .chain(
+            to.iter()
+                .filter(|line| !from.contains(line))
+                .map(|line| Diff::Added(line.clone())),
+        )
+        .collect()
+}
+
+impl BaseConfig {
+    pub(crate) fn key(&self) -> EntryKey {
+        (
+            self.name.clone(),
+            self.target_version
+                .clone()
+                .unwrap_or_else(|| self.version.clone()),
+        )
+    }
+}
+
+impl InstallationConfig {
+    pub(crate) fn key(&self) -> EntryKey {
+        (
+            self.name.clone(),
+            self.target_version
+                .clone()
+                .unwrap_or_else(|| self.version.clone()),
+        )
+    }
+
+    /// A line based representation of every step for comparisons
+    fn step_lines(&self) -> Vec<String> {
+        self.steps
+            .iter()
+            .enumerate()
+            .flat_map(|(i, step)| {
+                step.lines()
+                    .into_iter()
+                    .map(move |l| format!("step {i}: {l}"))
+            })
+            .collect()
+    }
+}
+
+impl Layer {
+    fn lines(&self) -> Vec<String> {
+        let installation = match &self.installation {
+            Installation::Docker(docker) => docker
+                .commands
+                .iter()
+                .map(|c| c.to_string())
+                .chain(
+                    docker
+                        .dependencies
+                        .iter()
+                        .map(|d| format!("dependency {d}")),
+                )
+                .collect::<Vec<_>>(),
+            Installation::Rpm(rpm) => rpm
+                .installation_methods
+                .iter()
+                .sorted_by_key(|(package_manager, _)| package_manager.to_string())
+                .flat_map(|(package_manager, method)| {
+                    method
+                        .script
+                        .iter()
+                        .map(move |line| format!("{package_manager}: {line}"))
+                })
+                .collect(),
+        };
+        installation
+            .into_iter()
+            .chain(
+                self.copy
+                    .iter()
+                    .sorted()
+                    .map(|(src, dest)| format!("copy {src} {dest}")),
+            )
+            .chain(self.build_args.iter().map(|arg| format!("build-arg {arg}")))
+            .chain(self.secrets.iter().map(|secret| format!("secret {secret}")))
+            .collect()
+    }
+}
+
+impl Changes {
+    fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            diffs: Vec::new(),
+            inner: Vec::new(),
+        }
+    }
+
+    /// A titled change for a single field, if the field changed
+    fn field(name: &str, diff: &Diff<String>) -> Option<Self> {
+        if diff.is_same() {
+            return None;
+        }
+        let mut field = Changes::new(name);
+        field.diffs.push(diff.clone());
+        Some(field)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.diffs.is_empty() && self.inner.is_empty()
+    }
+
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.title)?;
+        for diff in &self.diffs {
+            writeln!(f, "{:indent$}  {diff}", "")?;
+        }
+        for inner in &self.inner {
+            inner.fmt_indented(f, indent + 2)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Changes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+impl From<&LockChanges> for Changes {
+    fn from(changes: &LockChanges) -> Self {
+        let mut all = Changes::new("Lock changes");
+        all.inner
+            .extend(Changes::field("registry", &changes.registry));
+
+        let mut bases = Changes::new("Bases");
+        for base in changes.bases.iter().filter(|b| !b.is_same()) {
+            let mut inner = Changes::new(base.base.to_string());
+            if !base.base.is_added_or_removed() {
+                inner.inner.extend(
+                    [("identifier", &base.identifier), ("tag", &base.tag)]
+                        .into_iter()
+                        .filter_map(|(name, diff)| Changes::field(name, diff.as_ref()?)),
+                );
+                if base.platforms.values().any(|diff| !diff.is_same()) {
+                    let mut platforms = Changes::new("platforms");
+                    platforms.inner.extend(
+                        base.platforms
+                            .iter()
+                            .filter_map(|(platform, diff)| Changes::field(platform, diff)),
+                    );
+                    inner.inner.push(platforms);
+                }
+            }
+            bases.inner.push(inner);
+        }
+
+        let mut features = Changes::new("Features");
+        for feature in changes.features.iter().filter(|f| !f.is_same()) {
+            let mut inner = Changes::new(feature.feature.to_string());
+            if !feature.feature.is_added_or_removed() {
+                inner.inner.extend(
+                    feature
+                        .tag
+                        .as_ref()
+                        .and_then(|diff| Changes::field("tag", diff)),
+                );
+                if !feature.steps.is_empty() {
+                    let mut steps = Changes::new("steps");
+                    steps.diffs.extend(feature.steps.iter().cloned());
+                    inner.inner.push(steps);
+                }
+            }
+            features.inner.push(inner);
+        }
+
+        let mut builds = Changes::new("Builds");
+        for build in changes.build.iter().filter(|b| !b.is_same()) {
+            let mut inner = Changes::new(build.target.to_string());
+            if build.build.is_added_or_removed() {
+                inner.diffs.push(build.build.clone());
+            } else {
+                inner.inner.extend(
+                    [
+                        ("build", &build.build),
+                        ("image_name", &build.image_name),
+                        ("image_tag", &build.image_tag),
+                    ]
+                    .into_iter()
+                    .filter_map(|(name, diff)| Changes::field(name, diff)),
+                );
+                if build.tags.iter().any(|diff| !diff.is_same()) {
+                    let mut tags = Changes::new("alias_tags");
+                    tags.diffs
+                        .extend(build.tags.iter().filter(|d| !d.is_same()).cloned());
+                    inner.inner.push(tags);
+                }
+            }
+            builds.inner.push(inner);
         }
+
+        all.inner.extend(
+            [bases, features, builds]
+                .into_iter()
+                .filter(|changes| !changes.is_empty()),
+        );
+        all
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lock::ImageIdentifier,
+        test_lock::{base, build, feature, lock},
+    };
+
+    /// Ubuntu with Corretto, as the `java` and `java-slim` images
+    fn java() -> TuxWranglerConfigLocked {
+        let mut ubuntu = base("ubuntu", "22.04.4", "jammy");
+        ubuntu.platforms = [("linux/amd64", "sha256:a1"), ("linux/arm64", "sha256:a2")]
+            .map(|(platform, digest)| (platform.to_string(), digest.to_string()))
+            .into();
+        let corretto = feature("corretto", "21.0.4.7.1", "21");
+        let builds = vec![
+            build(&ubuntu, &[&corretto], "java", "21.0.4"),
+            build(&ubuntu, &[&corretto], "java-slim", "21.0.4"),
+        ];
+        lock(vec![ubuntu], vec![corretto], builds)
+    }
+
+    #[test]
+    fn identical_locks_have_no_changes() {
+        let changes = java().update_changes(java());
+        assert!(!changes.has_changes());
+        assert_eq!(changes.build.len(), 2);
+    }
+
+    #[test]
+    fn version_updates_keep_entries_matched_by_target_version() {
+        let mut next = java();
+        next.features[0].version = "21.0.5.7.1".to_string();
+        for build in &mut next.builds {
+            build.features[0].version = "21.0.5.7.1".to_string();
+        }
+        let changes = java().update_changes(next);
+        assert!(changes.has_changes());
+        let [feature] = &changes.features[..] else {
+            panic!("expected a single feature");
+        };
+        assert!(matches!(
+            &feature.feature,
+            Diff::Changed(from, to) if from.version == "21.0.4.7.1" && to.version == "21.0.5.7.1"
+        ));
+        assert!(feature.steps.is_empty());
+        assert!(changes.bases.iter().all(BaseDiff::is_same));
+        let [java, slim] = &changes.build[..] else {
+            panic!("expected 2 builds");
+        };
+        for build in [java, slim] {
+            assert!(matches!(build.build, Diff::Changed(..)));
+            assert!(build.target.is_same());
+            assert!(build.image_tag.is_same());
+        }
+    }
+
+    #[test]
+    fn reports_digest_refreshes() {
+        let mut next = java();
+        next.bases[0].identifier = ImageIdentifier::Digest {
+            digest: "sha256:bbbb".to_string(),
+        };
+        let changes = java().update_changes(next);
+        assert!(changes.has_changes());
+        let [base] = &changes.bases[..] else {
+            panic!("expected a single base");
+        };
+        assert!(base.base.is_same());
+        assert!(matches!(
+            &base.identifier,
+            Some(Diff::Changed(from, to)) if from == "@sha256:ubuntu-22.04.4" && to == "@sha256:bbbb"
+        ));
+        assert!(base.is_refreshed());
+        assert!(changes.build.iter().all(BuildDiff::is_same));
+    }
+
+    #[test]
+    fn reports_step_changes() {
+        let mut next = java();
+        next.features[0].steps = feature("corretto", "21.0.5.7.1", "21").steps;
+        let changes = java().update_changes(next);
+        assert!(changes.has_changes());
+        let [feature] = &changes.features[..] else {
+            panic!("expected a single feature");
+        };
+        assert!(feature.feature.is_same());
+        assert_eq!(
+            feature
+                .steps
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "- step 0: RUN install corretto-21.0.4.7.1",
+                "+ step 0: RUN install corretto-21.0.5.7.1"
+            ]
+        );
+        assert!(changes.build.iter().all(BuildDiff::is_same));
+    }
+
+    #[test]
+    fn builds_with_the_same_components_are_kept_apart() {
+        let mut next = java();
+        next.builds[1].image_tag = "21-slim".to_string();
+        let changes = java().update_changes(next);
+        let tags = changes
+            .build
+            .iter()
+            .map(|build| (build.image_name.to_string(), build.image_tag.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tags,
+            [
+                ("  java".to_string(), "  21.0.4".to_string()),
+                ("  java-slim".to_string(), "~ 21.0.4 -> 21-slim".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_added_and_removed_builds() {
+        let mut next = java();
+        next.builds.pop();
+        let changes = java().update_changes(next);
+        let removed = changes
+            .build
+            .iter()
+            .filter(|build| matches!(build.build, Diff::Removed(_)))
+            .count();
+        assert_eq!(removed, 1);
+        assert!(changes.has_changes());
+    }
+
+    #[test]
+    fn reports_platform_digest_changes() {
+        let mut next = java();
+        next.bases[0]
+            .platforms
+            .insert("linux/arm64".to_string(), "sha256:b2".to_string());
+        let changes = java().update_changes(next);
+        assert!(changes.has_changes());
+        let platforms = &changes.bases[0].platforms;
+        assert!(platforms["linux/amd64"].is_same());
+        assert!(matches!(
+            &platforms["linux/arm64"],
+            Diff::Changed(from, to) if from == "sha256:a2" && to == "sha256:b2"
+        ));
+        assert!(changes.bases[0].is_refreshed());
+        assert!(changes
+            .changelog()
+            .contains("| ubuntu | `22.04.4` | `linux/arm64` | `sha256:a2` | `sha256:b2` |"));
     }
 }
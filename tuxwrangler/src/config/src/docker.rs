@@ -24,43 +24,12 @@ impl Docker {
 
     pub(crate) async fn pull(&self, image: &str) -> Result<()> {
         trace!("Pulling image '{}'", image);
-        let creds = if let Some(registry) = Docker::registry(image).split('/').next() {
-            trace!("Identified registry '{}'", registry);
-            trace!("Checking for credentials");
-            Some(match docker_credential::get_credential(registry) {
-                Err(e) => {
-                    trace!("No credentials found for registry '{}': {}", registry, e);
-                    None
-                }
-                Ok(DockerCredential::IdentityToken(token)) => {
-                    trace!("Using provided Docker credentials: {}", token);
-                    Some(DockerCredentials {
-                        username: Some("oauth2accesstoken".to_string()),
-                        password: Some(token.clone()),
-                        identitytoken: Some(token),
-                        serveraddress: Some(registry.to_string()),
-                        ..Default::default()
-                    })
-                }
-                Ok(DockerCredential::UsernamePassword(username, password)) => {
-                    trace!(
-                        "Using provided Docker credentials: {}, {}",
-                        username,
-                        password
-                    );
-                    Some(DockerCredentials {
-                        username: Some(username),
-                        password: Some(password),
-                        serveraddress: Some(Docker::registry(image)),
-                        ..Default::default()
-                    })
-                }
-            })
-        } else {
-            trace!("No registry found in image '{}'", image);
-            trace!("No pull credentials will be used.");
-            None
-        };
+        let creds = Docker::credentials(
+            Docker::registry(image)
+                .split('/')
+                .next()
+                .expect("The image has a registry"),
+        );
 
         let mut stream = self.docker.create_image(
             Some(CreateImageOptions {
@@ -68,7 +37,7 @@ impl Docker {
                 ..Default::default()
             }),
             None,
-            creds.flatten(),
+            creds,
         );
         while let Some(_next) = stream.try_next().await? {
             // Wait for the image pull to complete
@@ -77,6 +46,44 @@ impl Docker {
         Ok(())
     }
 
+    /// Look up the Docker credentials stored for the given registry host
+    pub(crate) fn credentials(registry: &str) -> Option<DockerCredentials> {
+        trace!("Identified registry '{}'", registry);
+        trace!("Checking for credentials");
+        match docker_credential::get_credential(registry) {
+            Err(e) => {
+                trace!("No credentials found for registry '{}': {}", registry, e);
+                None
+            }
+            Ok(DockerCredential::IdentityToken(token)) => {
+                trace!(
+                    "Using the identity token stored for registry '{}'",
+                    registry
+                );
+                Some(DockerCredentials {
+                    username: Some("oauth2accesstoken".to_string()),
+                    password: Some(token.clone()),
+                    identitytoken: Some(token),
+                    serveraddress: Some(registry.to_string()),
+                    ..Default::default()
+                })
+            }
+            Ok(DockerCredential::UsernamePassword(username, password)) => {
+                trace!(
+                    "Using the credentials of '{}' stored for registry '{}'",
+                    username,
+                    registry
+                );
+                Some(DockerCredentials {
+                    username: Some(username),
+                    password: Some(password),
+                    serveraddress: Some(registry.to_string()),
+                    ..Default::default()
+                })
+            }
+        }
+    }
+
     pub(crate) fn registry(image: &str) -> String {
         image
             .split(":")
@@ -1,22 +1,97 @@
-use std::io::Write;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    env, fs,
+    io::Write,
+    path::Path,
+    process::Stdio,
+};
 
-use crate::{docker::Docker, lock::SingleVersioned, TuxWranglerConfigLocked};
-use anyhow::Result;
-use bollard::image::{BuildImageOptions, TagImageOptions};
-use futures::{future::join_all, TryStreamExt};
-use log::{debug, error, info, trace};
+use crate::{
+    default_jobs,
+    docker::Docker,
+    labels::Provenance,
+    lock::{BuildArg, Layer, Secret, SingleBuild, SingleVersioned},
+    platform::Platform,
+    registry::Registry,
+    TuxWranglerConfigLocked,
+};
+use anyhow::{anyhow, Context, Result};
+use bollard::{
+    image::{BuildImageOptions, TagImageOptions},
+    models::BuildInfoAux,
+};
+use futures::{stream::FuturesUnordered, StreamExt};
+use itertools::Itertools;
+use log::{debug, error, info, trace, warn};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+};
 
 use crate::docker_file::create_dockerfile_for;
 
+/// The number of build output lines attached to the error of a failed build
+const BUILD_LOG_LINES: usize = 20;
+
+/// The output of a running build, kept to describe the build if it fails
+#[derive(Default)]
+struct BuildLog {
+    /// The Dockerfile step that is currently running
+    step: Option<String>,
+    /// The most recent lines of build output
+    lines: VecDeque<String>,
+}
+
+impl BuildLog {
+    /// Record output from the daemon, keeping track of the current step
+    fn push(&mut self, output: &str) {
+        for line in output.lines().map(str::trim_end).filter(|l| !l.is_empty()) {
+            if line.starts_with("Step ") {
+                self.step = Some(line.to_string());
+            }
+            // BuildKit's plain progress output, e.g. `#7 [base 2/3] RUN dnf install -y git`
+            if let Some((_, step)) = line
+                .split_once(' ')
+                .filter(|(id, step)| id.starts_with('#') && step.starts_with('['))
+            {
+                self.step = Some(step.to_string());
+            }
+            if self.lines.len() == BUILD_LOG_LINES {
+                self.lines.pop_front();
+            }
+            self.lines.push_back(line.to_string());
+        }
+    }
+
+    /// Create an error for the failed build including the failing step and recent output
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("{}", message.trim_end())
+            .context(format!(
+                "Last {} lines of build output:\n{}",
+                self.lines.len(),
+                self.lines.iter().join("\n")
+            ))
+            .context(format!(
+                "Build failed at step '{}'",
+                self.step.as_deref().unwrap_or("unknown")
+            ))
+    }
+}
+
 impl Docker {
+    /// Build a labeled image for a platform, or the platform of the Docker daemon when there is none
     pub async fn build_image(
         &self,
         config: &TuxWranglerConfigLocked,
-        base: &SingleVersioned,
-        features: &[SingleVersioned],
+        build: &SingleBuild,
+        platform: Option<&str>,
         tag: &str,
+        labels: &BTreeMap<String, String>,
+        options: &BuildOptions,
     ) -> Result<()> {
-        let (dockerlines, dependencies) = create_dockerfile_for(config, base, features)?;
+        let features = &build.features;
+        let (dockerlines, dependencies) =
+            create_dockerfile_for(config, &build.base, features, platform, labels)?;
         let dockerfile = dockerlines.join("\n");
         trace!("Build Dockerfile: \n{dockerfile}");
         let mut header = tar::Header::new_gnu();
@@ -34,10 +109,25 @@ impl Docker {
         let mut c = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
         c.write_all(&uncompressed)?;
         let compressed = c.finish()?;
+
+        let build_args = config.build_arg_values(features, &self.home)?;
+        let secrets = config.secret_options(features, &self.home)?;
+        let mut log = BuildLog::default();
+        if !secrets.is_empty() || !options.cache_from.is_empty() || !options.cache_to.is_empty() {
+            let args = buildx_args(tag, platform, &build_args, &secrets, options);
+            return self.build_image_cli(compressed, &args, &mut log).await;
+        }
+
         let build_image_options = BuildImageOptions {
             t: tag,
             dockerfile: "Dockerfile",
-            pull: true,
+            pull: options.pull,
+            nocache: options.no_cache,
+            platform: platform.unwrap_or_default(),
+            buildargs: build_args
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect(),
             ..Default::default()
         };
 
@@ -45,17 +135,87 @@ impl Docker {
             .docker
             .build_image(build_image_options, None, Some(compressed.into()));
 
-        while let Some(bi) = build.try_next().await? {
+        while let Some(bi) = build.next().await {
+            let bi = bi.map_err(|e| log.error(&e.to_string()))?;
             trace!("Response: {:?}", bi);
+            if let Some(stream) = &bi.stream {
+                debug!("{}", stream.trim_end());
+                log.push(stream);
+            }
             if let Some(status) = bi.status {
                 debug!("{status}",)
             };
+            if let Some(BuildInfoAux::BuildKit(status)) = &bi.aux {
+                for vertex_log in &status.logs {
+                    log.push(&String::from_utf8_lossy(&vertex_log.msg));
+                }
+                for vertex in &status.vertexes {
+                    log.step = Some(vertex.name.clone());
+                    if !vertex.error.is_empty() {
+                        return Err(log.error(&vertex.error));
+                    }
+                }
+            }
+            if let Some(error) = bi
+                .error_detail
+                .and_then(|detail| detail.message)
+                .or(bi.error)
+            {
+                return Err(log.error(&error));
+            }
         }
 
         Ok(())
     }
 
-    async fn _tag_images(&self, image_name: &str, repo: &str, tags: &[String]) -> Result<()> {
+    /// Build with `docker buildx`, which provides the BuildKit secrets and cache export the API doesn't
+    async fn build_image_cli(
+        &self,
+        context: Vec<u8>,
+        args: &[String],
+        log: &mut BuildLog,
+    ) -> Result<()> {
+        debug!("Running 'docker {}'", args.join(" "));
+        let mut child = Command::new("docker")
+            .args(args)
+            // Read the context from stdin
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Unable to run docker")?;
+        let mut stdin = child.stdin.take().context("docker has no stdin")?;
+        let stderr = child.stderr.take().context("docker has no stderr")?;
+        let send = async move {
+            stdin.write_all(&context).await?;
+            // Close stdin so that docker starts the build
+            drop(stdin);
+            anyhow::Ok(())
+        };
+        let read = async {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Some(line) = lines.next_line().await? {
+                debug!("{line}");
+                log.push(&line);
+            }
+            anyhow::Ok(())
+        };
+        let (sent, read) = tokio::join!(send, read);
+        let status = child.wait().await?;
+        if !status.success() {
+            return Err(log.error(&format!("docker buildx build failed with {status}")));
+        }
+        sent.and(read)
+    }
+
+    pub(crate) async fn tag_images(
+        &self,
+        image_name: &str,
+        repo: &str,
+        tags: &[String],
+    ) -> Result<()> {
         for tag in tags {
             self.docker
                 .tag_image(
@@ -71,28 +231,420 @@ impl Docker {
     }
 }
 
+/// Options controlling how the images in a lock file are built
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    /// Only tag images with their build target
+    pub skip_tags: bool,
+    /// Push the images to the registry once every build has succeeded, `jobs` at a time
+    pub push: bool,
+    /// The maximum number of builds running at once
+    pub jobs: usize,
+    /// Continue with the remaining builds after a build fails
+    pub keep_going: bool,
+    /// BuildKit cache sources to import, `{target}` is replaced by the build target.
+    /// Builds for several platforms append the platform, as in `<target>-linux-arm64`.
+    pub cache_from: Vec<String>,
+    /// BuildKit cache destinations to export to, `{target}` is replaced like in `cache_from`
+    pub cache_to: Vec<String>,
+    /// Run every step instead of using cached layers
+    pub no_cache: bool,
+    /// Pull the base images even when they are available locally
+    pub pull: bool,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            skip_tags: false,
+            push: false,
+            jobs: default_jobs(),
+            keep_going: false,
+            cache_from: Vec::new(),
+            cache_to: Vec::new(),
+            no_cache: false,
+            pull: true,
+        }
+    }
+}
+
+/// The arguments of `docker buildx build` for an image, loading it into the Docker daemon.
+/// `tag` is the build target, with the platform appended for builds with several platforms.
+fn buildx_args(
+    tag: &str,
+    platform: Option<&str>,
+    build_args: &[(String, String)],
+    secrets: &[String],
+    options: &BuildOptions,
+) -> Vec<String> {
+    let mut args = [
+        "buildx",
+        "build",
+        "--progress=plain",
+        "--load",
+        "--file",
+        "Dockerfile",
+        "--tag",
+        tag,
+    ]
+    .map(String::from)
+    .to_vec();
+    if options.pull {
+        args.push("--pull".to_string());
+    }
+    if options.no_cache {
+        args.push("--no-cache".to_string());
+    }
+    if let Some(platform) = platform {
+        args.extend(["--platform".to_string(), platform.to_string()]);
+    }
+    for (name, value) in build_args {
+        args.extend(["--build-arg".to_string(), format!("{name}={value}")]);
+    }
+    for secret in secrets {
+        args.extend(["--secret".to_string(), secret.clone()]);
+    }
+    for cache in &options.cache_from {
+        args.extend(["--cache-from".to_string(), cache.replace("{target}", tag)]);
+    }
+    for cache in &options.cache_to {
+        args.extend(["--cache-to".to_string(), cache.replace("{target}", tag)]);
+    }
+    args
+}
+
+impl BuildArg {
+    fn value(&self, home: &Path) -> Result<String> {
+        match self {
+            BuildArg::Env(name) => {
+                env::var(name).context(format!("The environment variable '{name}' is not set"))
+            }
+            BuildArg::File(path) => fs::read_to_string(home.join(path))
+                .map(|value| value.trim_end_matches(['\r', '\n']).to_string())
+                .context(format!("Unable to read '{}'", path.display())),
+            BuildArg::Value(value) => Ok(value.clone()),
+        }
+    }
+}
+
+impl Secret {
+    /// The `--secret` option of `docker build` for this secret
+    fn option(&self, id: &str, home: &Path) -> Result<String> {
+        match self {
+            Secret::Env(name) => {
+                env::var_os(name)
+                    .context(format!("The environment variable '{name}' is not set"))?;
+                Ok(format!("id={id},env={name}"))
+            }
+            Secret::File(path) => {
+                let path = home.join(path);
+                if !path.is_file() {
+                    return Err(anyhow!("'{}' is not a file", path.display()));
+                }
+                Ok(format!("id={id},src={}", path.display()))
+            }
+        }
+    }
+}
+
 impl TuxWranglerConfigLocked {
-    pub(crate) async fn build_images(&self, docker: &Docker, skip_tags: bool) -> Result<()> {
-        info!("Building images");
-        join_all(self.builds.iter().map(|build| async move {
-            info!("Build started for: {build}");
-            let tag = &build.target;
+    /// The names of the build arguments and secrets used by the steps of the features
+    fn step_references<'a>(
+        &'a self,
+        features: &'a [SingleVersioned],
+        references: fn(&Layer) -> &Vec<String>,
+    ) -> impl Iterator<Item = &'a String> {
+        features
+            .iter()
+            .filter_map(|feature| self.feature(feature))
+            .flat_map(move |feature| feature.steps.iter().flat_map(references))
+            .unique()
+    }
+
+    /// The value of each build argument used by the features
+    fn build_arg_values(
+        &self,
+        features: &[SingleVersioned],
+        home: &Path,
+    ) -> Result<Vec<(String, String)>> {
+        self.step_references(features, |step| &step.build_args)
+            .map(|name| {
+                self.build_args
+                    .get(name)
+                    .context(format!("Build argument '{name}' is not defined"))?
+                    .value(home)
+                    .context(format!("Unable to read build argument '{name}'"))
+                    .map(|value| (name.clone(), value))
+            })
+            .collect()
+    }
+
+    /// The `--secret` options for each secret used by the features
+    fn secret_options(&self, features: &[SingleVersioned], home: &Path) -> Result<Vec<String>> {
+        self.step_references(features, |step| &step.secrets)
+            .map(|name| {
+                self.secrets
+                    .get(name)
+                    .context(format!("Secret '{name}' is not defined"))?
+                    .option(name, home)
+                    .context(format!("Unable to read secret '{name}'"))
+            })
+            .collect()
+    }
+
+    pub(crate) async fn build_images(
+        &self,
+        docker: &Docker,
+        registry: &mut Registry,
+        options: &BuildOptions,
+    ) -> Result<()> {
+        info!("Building images with {} concurrent jobs", options.jobs);
+        let provenance = Provenance::new(&docker.home);
+        let (builds, parents) = self.build_order();
+        let mut dependents = vec![Vec::new(); builds.len()];
+        let mut ready = VecDeque::new();
+        for (i, parent) in parents.into_iter().enumerate() {
+            match parent {
+                Some(parent) => dependents[parent].push(i),
+                None => ready.push_back(i),
+            }
+        }
+        let provenance = &provenance;
+        let mut running = FuturesUnordered::new();
+        let mut failures = Vec::<anyhow::Error>::new();
+        let mut skipped = 0;
+        loop {
+            while running.len() < options.jobs.max(1) {
+                let Some(i) = ready.pop_front() else { break };
+                let build = builds[i];
+                running.push(async move {
+                    (
+                        i,
+                        self.build_single(docker, build, options, provenance).await,
+                    )
+                });
+            }
+            let Some((i, result)) = running.next().await else {
+                break;
+            };
+            match result {
+                Ok(()) => ready.extend(&dependents[i]),
+                Err(e) => {
+                    failures.push(e);
+                    if !options.keep_going {
+                        warn!("Stopping remaining builds after a failure");
+                        break;
+                    }
+                    // Builds reusing the stages of a failed build would fail the same way
+                    let mut skip = dependents[i].clone();
+                    while let Some(j) = skip.pop() {
+                        warn!(
+                            "Skipped build of {} after the build of {} failed",
+                            builds[j], builds[i]
+                        );
+                        skipped += 1;
+                        skip.extend(&dependents[j]);
+                    }
+                }
+            }
+        }
+        let failed = failures.len();
+        if let Some(failure) = failures.into_iter().next() {
+            let total = self.builds.len();
+            let mut summary = format!("{failed} of {total} builds failed");
+            if skipped > 0 {
+                summary += &format!(", {skipped} skipped");
+            }
+            return Err(failure.context(summary));
+        }
+        if options.skip_tags {
+            info!("Skipped image tagging");
+        }
+        if options.push {
+            self.push_images(docker, registry, options.jobs).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Build and tag a single image for each of its platforms
+    async fn build_single(
+        &self,
+        docker: &Docker,
+        build: &SingleBuild,
+        options: &BuildOptions,
+        provenance: &Provenance,
+    ) -> Result<()> {
+        for platform in build.platforms() {
+            let description = match platform {
+                Some(platform) => format!("{build} ({platform})"),
+                None => build.to_string(),
+            };
+            info!("Build started for: {description}");
+            let tag = &Platform::tag(&build.target, platform);
             docker
-                .build_image(self, &build.base, &build.features, tag)
+                .build_image(
+                    self,
+                    build,
+                    platform,
+                    tag,
+                    &self.labels(build, platform, provenance),
+                    options,
+                )
                 .await
-                .inspect(|_| info!("Build completed for: {build}"))
-                .inspect_err(|_| {
-                    error!("Build failed for : {build}");
-                })
-        }))
-        .await
-        .into_iter()
-        .collect::<Result<()>>()?;
-        if skip_tags {
-            info!("Skipping image tagging");
-            return Ok(());
+                .context(format!("Build failed for: {description} (target '{tag}')"))
+                .inspect(|_| info!("Build completed for: {description}"))
+                .inspect_err(|e| {
+                    error!("{e:?}");
+                })?;
+            if !options.skip_tags {
+                let tags = build
+                    .tags()
+                    .iter()
+                    .map(|t| Platform::tag(t, platform))
+                    .collect::<Vec<_>>();
+                debug!("Tagging '{tag}' as '{}:{:?}'", build.image_name, tags);
+                docker.tag_images(tag, &build.image_name, &tags).await?;
+            }
         }
-
         Ok(())
     }
+
+    /// Order the builds so that stages shared between builds are built once before they are reused.
+    /// A stage is a base followed by one or more of the features of a build, in order.
+    /// Each build waits for its parent, the first build containing the longest stage it shares with an earlier build.
+    fn build_order(&self) -> (Vec<&SingleBuild>, Vec<Option<usize>>) {
+        let builds = self
+            .builds
+            .iter()
+            .sorted_by_key(|build| build.to_string())
+            .collect::<Vec<_>>();
+        // The index of the first build containing each stage
+        let mut first = HashMap::new();
+        let parents = builds
+            .iter()
+            .enumerate()
+            .map(|(i, build)| {
+                let stages = (1..=build.features.len())
+                    .map(|len| (&build.base, &build.features[..len]))
+                    .collect::<Vec<_>>();
+                let parent = stages
+                    .iter()
+                    .rev()
+                    .find_map(|stage| first.get(stage).copied());
+                for stage in stages {
+                    first.entry(stage).or_insert(i);
+                }
+                parent
+            })
+            .collect();
+        (builds, parents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_lock::{base, build, feature, lock};
+
+    #[test]
+    fn builds_wait_for_the_longest_shared_stage() {
+        let ubuntu = base("ubuntu", "22.04", "22.04");
+        let alpine = base("alpine", "3.20", "3.20");
+        let java = feature("java", "21", "21");
+        let tomcat_10 = feature("tomcat", "10", "10");
+        let tomcat_9 = feature("tomcat", "9", "9");
+        let builds = vec![
+            build(&ubuntu, &[&java, &tomcat_10], "tomcat", "10"),
+            build(&ubuntu, &[&java, &tomcat_9], "tomcat", "9"),
+            build(&ubuntu, &[&java, &tomcat_10, &tomcat_9], "tomcat", "both"),
+            build(&ubuntu, &[&java], "java", "21"),
+            build(&alpine, &[&java], "java", "21-alpine"),
+            build(&ubuntu, &[], "ubuntu", "22.04"),
+        ];
+        let locked = lock(
+            vec![ubuntu, alpine],
+            vec![java, tomcat_10, tomcat_9],
+            builds,
+        );
+        let (builds, parents) = locked.build_order();
+        let order = builds
+            .iter()
+            .zip(parents)
+            .map(|(build, parent)| {
+                (
+                    build.target.as_str(),
+                    parent.map(|i| builds[i].target.as_str()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                ("alpine-3.20-java-21", None),
+                ("ubuntu-22.04", None),
+                ("ubuntu-22.04-java-21", None),
+                (
+                    "ubuntu-22.04-java-21-tomcat-10",
+                    Some("ubuntu-22.04-java-21")
+                ),
+                (
+                    "ubuntu-22.04-java-21-tomcat-10-tomcat-9",
+                    Some("ubuntu-22.04-java-21-tomcat-10")
+                ),
+                (
+                    "ubuntu-22.04-java-21-tomcat-9",
+                    Some("ubuntu-22.04-java-21")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn buildx_args_fill_in_the_cache_target() {
+        let options = BuildOptions {
+            cache_from: vec!["type=registry,ref=cache:{target}".to_string()],
+            cache_to: vec!["type=local,dest=.cache/{target},mode=max".to_string()],
+            no_cache: true,
+            pull: false,
+            ..Default::default()
+        };
+        let args = buildx_args(
+            "java-21-linux-arm64",
+            Some("linux/arm64"),
+            &[("JAVA_HOME".to_string(), "/opt/java".to_string())],
+            &["id=token,env=TOKEN".to_string()],
+            &options,
+        );
+        assert_eq!(
+            args,
+            [
+                "buildx",
+                "build",
+                "--progress=plain",
+                "--load",
+                "--file",
+                "Dockerfile",
+                "--tag",
+                "java-21-linux-arm64",
+                "--no-cache",
+                "--platform",
+                "linux/arm64",
+                "--build-arg",
+                "JAVA_HOME=/opt/java",
+                "--secret",
+                "id=token,env=TOKEN",
+                "--cache-from",
+                "type=registry,ref=cache:java-21-linux-arm64",
+                "--cache-to",
+                "type=local,dest=.cache/java-21-linux-arm64,mode=max",
+            ]
+        );
+    }
+
+    #[test]
+    fn buildx_args_pull_by_default() {
+        let args = buildx_args("java-21", None, &[], &[], &BuildOptions::default());
+        assert_eq!(args[7..], ["java-21", "--pull"]);
+    }
 }
@@ -1,13 +1,15 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use crate::{
+    labels::{label_instruction, Provenance},
     lock::{
         BaseConfig, DockerInstallation, Installation, InstallationConfig, LayerType,
         RpmInstallationMethod, SingleVersioned,
     },
     TuxWranglerConfigLocked,
 };
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
 
 /// The dockerfile as a set of lines for easier manipulation
 type Dockerfile = Vec<String>;
@@ -50,17 +52,23 @@ impl Layer {
 
 /// Create a dockerfile for all targets in a locked config file
 /// TODO: This will be useful once https://github.com/fussybeaver/bollard/issues/391 enables specifying a build target
-pub fn create_dockerfile(config: &TuxWranglerConfigLocked) -> Result<(Dockerfile, Dependencies)> {
+pub(crate) fn create_dockerfile(
+    config: &TuxWranglerConfigLocked,
+    provenance: &Provenance,
+) -> Result<(Dockerfile, Dependencies)> {
     let mut layer_names = HashSet::new();
 
     let mut layers = Vec::new();
     let mut dependencies = HashSet::new();
 
     for build in &config.builds {
-        let base = base_layer(config.base(&build.base).context(format!(
-            "Base {}-{} is missing from configuration",
-            build.base.name, build.base.version
-        ))?);
+        let base = base_layer(
+            config.base(&build.base).context(format!(
+                "Base {}-{} is missing from configuration",
+                build.base.name, build.base.version
+            ))?,
+            None,
+        );
         if layer_names.insert(base.name.clone()) {
             layers.extend(base.lines)
         }
@@ -95,18 +103,22 @@ pub fn create_dockerfile(config: &TuxWranglerConfigLocked) -> Result<(Dockerfile
         }
 
         if layer_names.insert(build.target.clone()) {
-            layers.extend(tag_layer(&prev_layer, &build.target))
+            layers.extend(tag_layer(&prev_layer, &build.target));
+            layers.extend(label_instruction(&config.labels(build, None, provenance)));
         }
     }
 
     Ok((layers, dependencies.into_iter().collect()))
 }
 
-/// Create a dockerfile for the given base and features using the lock file
+/// Create a dockerfile for the given base and features using the lock file, labeling the final stage.
+/// The base image of a platform is used when one is locked for it.
 pub fn create_dockerfile_for(
     config: &TuxWranglerConfigLocked,
     base: &SingleVersioned,
     features: &[SingleVersioned],
+    platform: Option<&str>,
+    labels: &BTreeMap<String, String>,
 ) -> Result<(Dockerfile, Dependencies)> {
     // Keep track of each layer
     let mut layers = Vec::new();
@@ -114,10 +126,13 @@ pub fn create_dockerfile_for(
     let mut dependencies = HashSet::new();
 
     // Create a layer for the base
-    let base_layer = base_layer(config.base(base).context(format!(
-        "Base {}-{} is missing from configuration",
-        base.name, base.version
-    ))?);
+    let base_layer = base_layer(
+        config.base(base).context(format!(
+            "Base {}-{} is missing from configuration",
+            base.name, base.version
+        ))?,
+        platform,
+    );
     layers.extend(base_layer.lines);
     // Determine the package manager for rmp based feature installs
     let package_manager = config.package_manager_for_base(base).context(format!(
@@ -148,18 +163,23 @@ pub fn create_dockerfile_for(
             dependencies.extend(layer.dependencies);
         }
     }
+    layers.extend(label_instruction(labels));
 
     Ok((layers, dependencies.into_iter().collect()))
 }
 
 /// Create a dockerfile layer for a base (base image)
-fn base_layer(base: &BaseConfig) -> Layer {
+fn base_layer(base: &BaseConfig, platform: Option<&str>) -> Layer {
     let layer_name = base.tag.to_owned().unwrap_or_else(|| "temp".to_string());
+    let identifier = match platform.and_then(|platform| base.platforms.get(platform)) {
+        Some(digest) => format!("@{digest}"),
+        None => base.identifier.to_string(),
+    };
     Layer::new(
         layer_name.clone(),
         vec![format!(
             "FROM {}{} as {}\n",
-            base.registry, base.identifier, layer_name
+            base.registry, identifier, layer_name
         )],
     )
 }
@@ -207,7 +227,24 @@ fn installation_layers(
                     .collect(),
                 Default::default(),
             ))
-            .extend(installation_inner(package_manager, &layer.installation)?),
+            .extend((
+                layer
+                    .build_args
+                    .iter()
+                    .map(|arg| format!("ARG {arg}"))
+                    .collect(),
+                Default::default(),
+            ))
+            .extend(
+                mount_secrets(
+                    installation_inner(package_manager, &layer.installation)?,
+                    &layer.secrets,
+                )
+                .context(format!(
+                    "Unable to use the secrets of step {i} of '{}'",
+                    installation.name
+                ))?,
+            ),
         );
         copies.insert(layer_name.clone(), layer.copy.clone());
     }
@@ -258,6 +295,56 @@ fn installation_inner(
     })
 }
 
+/// Mount the secrets into every `RUN` instruction of an installation.
+/// Lines continuing an instruction are left alone, even when they start with `run`.
+fn mount_secrets(
+    (lines, dependencies): (Dockerfile, Dependencies),
+    secrets: &[String],
+) -> Result<(Dockerfile, Dependencies)> {
+    if secrets.is_empty() {
+        return Ok((lines, dependencies));
+    }
+    let mounts = secrets
+        .iter()
+        .map(|secret| format!("--mount=type=secret,id={secret}"))
+        .join(" ");
+    let mut mounted = false;
+    let mut continued = false;
+    let lines = lines
+        .into_iter()
+        .map(|command| {
+            command
+                .split('\n')
+                .map(|line| {
+                    let instruction = !continued;
+                    continued = line.trim_end().ends_with('\\');
+                    let trimmed = line.trim_start();
+                    match trimmed.split_once(char::is_whitespace) {
+                        Some((keyword, rest))
+                            if instruction && keyword.eq_ignore_ascii_case("RUN") =>
+                        {
+                            mounted = true;
+                            let indent = &line[..line.len() - trimmed.len()];
+                            format!("{indent}{keyword} {mounts} {}", rest.trim_start())
+                        }
+                        _ => line.to_string(),
+                    }
+                })
+                .join("\n")
+        })
+        .collect();
+    if !mounted {
+        return Err(anyhow!(
+            "The secrets {} are not used by any `RUN` instruction",
+            secrets
+                .iter()
+                .map(|secret| format!("'{secret}'"))
+                .join(", ")
+        ));
+    }
+    Ok((lines, dependencies))
+}
+
 /// Create the dockerfile and dependencies for a docker installation
 fn docker_installation(docker_config: &DockerInstallation) -> (Dockerfile, Dependencies) {
     (
@@ -0,0 +1,111 @@
+use std::cell::Cell;
+
+use crate::{
+    docker::Docker, lock::SingleBuild, platform::Platform, registry::Registry,
+    TuxWranglerConfigLocked,
+};
+use anyhow::{anyhow, Result};
+use bollard::image::PushImageOptions;
+use futures::{future, stream, StreamExt, TryStreamExt};
+use log::{debug, error, info, trace};
+
+impl Docker {
+    /// Push a tag of a local image to its registry.
+    /// The repository must already include the registry host, e.g. `registry.example.com/java`.
+    pub(crate) async fn push_image(&self, repo: &str, tag: &str) -> Result<()> {
+        debug!("Pushing image '{repo}:{tag}'");
+        let creds = Docker::credentials(repo.split('/').next().unwrap_or(repo));
+        let mut push = self
+            .docker
+            .push_image(repo, Some(PushImageOptions { tag }), creds);
+
+        while let Some(pi) = push.try_next().await? {
+            trace!("Response: {:?}", pi);
+            if let Some(error) = pi.error {
+                return Err(anyhow!(error).context(format!("Unable to push '{repo}:{tag}'")));
+            }
+            if let Some(status) = pi.status {
+                debug!("{status}")
+            };
+        }
+
+        Ok(())
+    }
+}
+
+impl TuxWranglerConfigLocked {
+    /// Tag a single build for the configured registry and push each of its platforms
+    async fn push_build(&self, docker: &Docker, build: &SingleBuild) -> Result<()> {
+        let repo = build.repository(&self.registry);
+        for platform in build.platforms() {
+            let tags = build
+                .tags()
+                .iter()
+                .map(|tag| Platform::tag(tag, platform))
+                .collect::<Vec<_>>();
+            info!("Push started for: {repo}:{}", tags.join(","));
+            docker
+                .tag_images(&Platform::tag(&build.target, platform), &repo, &tags)
+                .await?;
+            for tag in &tags {
+                docker
+                    .push_image(&repo, tag)
+                    .await
+                    .inspect(|_| info!("Push completed for: {repo}:{tag}"))
+                    .inspect_err(|_| {
+                        error!("Push failed for : {build}");
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tag every build for the configured registry and push it, with at most `jobs` builds pushing at once.
+    /// Images are expected to have been built locally with their `target` as the tag.
+    /// Builds for several platforms push an image for each platform (`<tag>-linux-arm64`)
+    /// and a manifest list combining them as each tag.
+    pub(crate) async fn push_images(
+        &self,
+        docker: &Docker,
+        registry: &mut Registry,
+        jobs: usize,
+    ) -> Result<()> {
+        info!("Pushing images to '{}'", self.registry);
+        // No more pushes start after a failure, the ones already running are finished
+        let failed = Cell::new(false);
+        let mut pushes = stream::iter(&self.builds)
+            .take_while(|_| future::ready(!failed.get()))
+            .map(|build| self.push_build(docker, build))
+            .buffer_unordered(jobs.max(1));
+        let mut failure = None;
+        while let Some(result) = pushes.next().await {
+            if let Err(e) = result {
+                failed.set(true);
+                failure.get_or_insert(e);
+            }
+        }
+        if let Some(failure) = failure {
+            return Err(failure);
+        }
+
+        for build in self
+            .builds
+            .iter()
+            .filter(|build| !build.platforms.is_empty())
+        {
+            let repo = build.repository(&self.registry);
+            for tag in build.tags() {
+                let images = build
+                    .platforms
+                    .iter()
+                    .map(|platform| (platform.clone(), Platform::tag(&tag, Some(platform))))
+                    .collect::<Vec<_>>();
+                registry
+                    .push_manifest_list(&repo, &tag, &images)
+                    .await
+                    .inspect(|_| info!("Pushed manifest list for: {repo}:{tag}"))?;
+            }
+        }
+        Ok(())
+    }
+}
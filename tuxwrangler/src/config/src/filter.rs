@@ -0,0 +1,227 @@
+use anyhow::Context;
+use log::warn;
+use regex::Regex;
+
+use crate::{
+    lock::{SingleBuild, SingleVersioned},
+    Result, TuxWranglerConfigLocked,
+};
+
+/// Selects a subset of the builds in a lock file.
+/// A build is selected when it matches at least one pattern of every non-empty filter.
+#[derive(Debug, Clone, Default)]
+pub struct BuildFilter {
+    /// Globs matched against the build target
+    pub targets: Vec<String>,
+    /// Bases as `name` or `name=<version glob>`
+    pub bases: Vec<String>,
+    /// Features as `name` or `name=<version glob>`
+    pub features: Vec<String>,
+    /// Globs matched against the rendered image name
+    pub image_names: Vec<String>,
+    /// Globs matched against the rendered image tag and alias tags
+    pub image_tags: Vec<String>,
+}
+
+/// A compiled `name[=version]` pattern
+struct VersionedPattern {
+    name: Regex,
+    version: Option<Regex>,
+}
+
+impl VersionedPattern {
+    fn new(pattern: &str) -> Result<Self> {
+        let (name, version) = match pattern.split_once('=') {
+            Some((name, version)) => (name, Some(glob(version)?)),
+            None => (pattern, None),
+        };
+        Ok(Self {
+            name: glob(name)?,
+            version,
+        })
+    }
+
+    /// Match against the actual version as well as the target version from the config
+    fn matches(&self, versioned: &SingleVersioned, target_version: Option<&String>) -> bool {
+        self.name.is_match(&versioned.name)
+            && self.version.as_ref().is_none_or(|version| {
+                version.is_match(&versioned.version)
+                    || target_version.is_some_and(|target| version.is_match(target))
+            })
+    }
+}
+
+/// Convert a glob (`*` and `?` wildcards) into an anchored regex
+fn glob(pattern: &str) -> Result<Regex> {
+    Regex::new(&format!(
+        "^{}$",
+        regex::escape(pattern)
+            .replace("\\*", ".*")
+            .replace("\\?", ".")
+    ))
+    .context(format!("Invalid pattern '{pattern}'"))
+}
+
+fn globs(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns.iter().map(|p| glob(p)).collect()
+}
+
+impl BuildFilter {
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+            && self.bases.is_empty()
+            && self.features.is_empty()
+            && self.image_names.is_empty()
+            && self.image_tags.is_empty()
+    }
+}
+
+impl TuxWranglerConfigLocked {
+    /// Remove every build that is not selected by the filter
+    pub fn filter_builds(mut self, filter: &BuildFilter) -> Result<Self> {
+        if filter.is_empty() {
+            return Ok(self);
+        }
+        let builds = std::mem::take(&mut self.builds);
+        let targets = globs(&filter.targets)?;
+        let image_names = globs(&filter.image_names)?;
+        let image_tags = globs(&filter.image_tags)?;
+        let bases = filter
+            .bases
+            .iter()
+            .map(|p| VersionedPattern::new(p))
+            .collect::<Result<Vec<_>>>()?;
+        let features = filter
+            .features
+            .iter()
+            .map(|p| VersionedPattern::new(p))
+            .collect::<Result<Vec<_>>>()?;
+
+        let selected = |build: &SingleBuild| {
+            let any = |patterns: &[Regex], value: &str| {
+                patterns.is_empty() || patterns.iter().any(|p| p.is_match(value))
+            };
+            let base_target = self
+                .base(&build.base)
+                .and_then(|b| b.target_version.as_ref());
+            any(&targets, &build.target)
+                && any(&image_names, &build.image_name)
+                && (image_tags.is_empty() || build.tags().iter().any(|tag| any(&image_tags, tag)))
+                && (bases.is_empty() || bases.iter().any(|p| p.matches(&build.base, base_target)))
+                && (features.is_empty()
+                    || features.iter().any(|p| {
+                        build.features.iter().any(|feature| {
+                            let target = self
+                                .feature(feature)
+                                .and_then(|f| f.target_version.as_ref());
+                            p.matches(feature, target)
+                        })
+                    }))
+        };
+        let builds = builds
+            .into_iter()
+            .filter(|build| selected(build))
+            .collect::<Vec<_>>();
+        if builds.is_empty() {
+            warn!("No builds matched the provided filters");
+        }
+        self.builds = builds;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_lock::{base, build, feature, lock};
+
+    /// Corretto 21 and 17 on Ubuntu, and plain Fedora
+    fn java() -> TuxWranglerConfigLocked {
+        let ubuntu = base("ubuntu", "22.04.4", "jammy");
+        let fedora = base("fedora", "40", "40");
+        let corretto_21 = feature("corretto", "21.0.4.7.1", "21");
+        let corretto_17 = feature("corretto", "17.0.12.7.1", "17");
+        let mut latest = build(&ubuntu, &[&corretto_21], "java", "21-jammy");
+        latest.alias_tags = vec!["21".to_string(), "latest".to_string()];
+        let builds = vec![
+            latest,
+            build(&ubuntu, &[&corretto_17], "java", "17-jammy"),
+            build(&fedora, &[], "fedora", "40"),
+        ];
+        lock(vec![ubuntu, fedora], vec![corretto_21, corretto_17], builds)
+    }
+
+    fn targets(filter: BuildFilter) -> Vec<String> {
+        java()
+            .filter_builds(&filter)
+            .unwrap()
+            .builds
+            .into_iter()
+            .map(|build| build.target)
+            .collect()
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn an_empty_filter_keeps_every_build() {
+        assert_eq!(targets(BuildFilter::default()).len(), 3);
+    }
+
+    #[test]
+    fn matches_target_globs() {
+        let filter = BuildFilter {
+            targets: patterns(&["ubuntu-*-corretto-2?"]),
+            ..Default::default()
+        };
+        assert_eq!(targets(filter), ["ubuntu-jammy-corretto-21"]);
+    }
+
+    #[test]
+    fn globs_are_anchored_and_literal() {
+        let filter = BuildFilter {
+            targets: patterns(&["fedora", "ubuntu-jammy-corretto-1."]),
+            ..Default::default()
+        };
+        assert!(targets(filter).is_empty());
+    }
+
+    #[test]
+    fn matches_actual_and_target_versions() {
+        let actual = BuildFilter {
+            bases: patterns(&["ubuntu=22.04.*"]),
+            ..Default::default()
+        };
+        let target = BuildFilter {
+            bases: patterns(&["ubuntu=jammy"]),
+            ..Default::default()
+        };
+        assert_eq!(targets(actual), targets(target));
+        let features = BuildFilter {
+            features: patterns(&["corretto=17"]),
+            ..Default::default()
+        };
+        assert_eq!(targets(features), ["ubuntu-jammy-corretto-17"]);
+    }
+
+    #[test]
+    fn matches_alias_tags() {
+        let filter = BuildFilter {
+            image_tags: patterns(&["latest"]),
+            ..Default::default()
+        };
+        assert_eq!(targets(filter), ["ubuntu-jammy-corretto-21"]);
+    }
+
+    #[test]
+    fn requires_every_filter() {
+        let filter = BuildFilter {
+            image_names: patterns(&["java"]),
+            features: patterns(&["corretto=21*", "corretto=11"]),
+            ..Default::default()
+        };
+        assert_eq!(targets(filter), ["ubuntu-jammy-corretto-21"]);
+    }
+}
@@ -0,0 +1,147 @@
+use std::{collections::HashMap, path::Path, process::Stdio, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use log::{debug, info};
+use tokio::process::Command;
+
+use crate::{cache::Cache, config::VersionFrom};
+
+const MAX_RETRIES: u32 = 5;
+const BASE_BACKOFF_S: u64 = 1;
+
+/// Lists the tags and branches of any git repository with `git ls-remote`
+pub struct Git {
+    cache: HashMap<(String, String), Vec<String>>,
+    disk_cache: Cache,
+}
+
+impl Git {
+    pub fn new(disk_cache: Cache) -> Self {
+        Self {
+            cache: Default::default(),
+            disk_cache,
+        }
+    }
+
+    pub(crate) async fn refs(
+        &mut self,
+        url: &str,
+        version_from: &VersionFrom,
+    ) -> Result<Vec<String>> {
+        let kind = match version_from {
+            VersionFrom::Tag => "--tags",
+            VersionFrom::Branch => "--heads",
+            VersionFrom::Release => {
+                return Err(anyhow!(
+                    "'{url}' is a git repository, releases are only available from GitHub"
+                ))
+            }
+        };
+        let key = (url.to_string(), kind.to_string());
+        if let Some(refs) = self.cache.get(&key) {
+            debug!("Using cached git refs for '{url}'");
+            return Ok(refs.clone());
+        }
+        let disk_key = format!("{kind} {url}");
+        if let Some(refs) = self.disk_cache.get::<Vec<String>>("git", &disk_key) {
+            self.cache.insert(key, refs.clone());
+            return Ok(refs);
+        }
+        self.disk_cache.check_online("git", &disk_key)?;
+        info!("Listing refs of '{url}'");
+        let mut retry = 0;
+        while retry < MAX_RETRIES {
+            match ls_remote(url, kind, None).await {
+                Ok(refs) => {
+                    self.disk_cache.set("git", &disk_key, &refs);
+                    self.cache.insert(key, refs.clone());
+                    return Ok(refs);
+                }
+                Err(e) => debug!("Failed to list refs: '{:?}'", e),
+            }
+            retry += 1;
+            tokio::time::sleep(Duration::from_secs(BASE_BACKOFF_S * 2_u64.pow(retry))).await;
+        }
+        Err(anyhow!(
+            "Unable to list refs of '{url}' after '{retry}' retries."
+        ))
+    }
+}
+
+/// Run `git ls-remote` in `current_dir`, or the working directory of the process when there is none
+async fn ls_remote(url: &str, kind: &str, current_dir: Option<&Path>) -> Result<Vec<String>> {
+    let mut command = Command::new("git");
+    if let Some(dir) = current_dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .args(["ls-remote", kind, "--", url])
+        // Fail instead of waiting for credentials
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Unable to run git")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git ls-remote failed for '{url}':\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter_map(|(_, reference)| {
+            reference
+                .strip_prefix("refs/tags/")
+                .or_else(|| reference.strip_prefix("refs/heads/"))
+        })
+        // Annotated tags are listed again as the commit they point to
+        .map(|name| name.trim_end_matches("^{}").to_string())
+        .unique()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, process::Command};
+
+    use super::*;
+
+    /// A temporary directory, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn urls_are_never_options() {
+        // Without `--`, git would run the upload pack against the `origin` of the working directory
+        let dir =
+            TempDir(std::env::temp_dir().join(format!("tuxwrangler-git-{}", std::process::id())));
+        let _ = fs::remove_dir_all(&dir.0);
+        fs::create_dir_all(&dir.0).unwrap();
+        let git = |args: &[&str]| {
+            assert!(Command::new("git")
+                .args(args)
+                .current_dir(&dir.0)
+                .output()
+                .unwrap()
+                .status
+                .success());
+        };
+        git(&["init", "--quiet", "--bare", "origin.git"]);
+        git(&["init", "--quiet", "."]);
+        git(&["remote", "add", "origin", "origin.git"]);
+
+        let marker = dir.0.join("marker");
+        let url = format!("--upload-pack=touch {}", marker.display());
+        assert!(ls_remote(&url, "--tags", Some(&dir.0)).await.is_err());
+        assert!(!marker.exists());
+    }
+}
@@ -1,48 +1,76 @@
-use std::{collections::HashMap, env, time::Duration};
+use std::{collections::HashMap, env, fs, path::PathBuf, time::Duration};
 
-use anyhow::{anyhow, Result};
-use log::{debug, info};
-use octocrab::Octocrab;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use octocrab::{models::repos::Release, Octocrab};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
 
-use crate::{config::VersionFrom, version::find_tag};
+use crate::{
+    cache::Cache,
+    config::{GithubFetchVersion, VersionFrom},
+    version::find_tag,
+};
+/// The API used when a fetch version has no `host`
+pub(crate) const GITHUB_API: &str = "https://api.github.com";
 const MAX_PAGES: u8 = 4;
+const RELEASES_PER_PAGE: u8 = 100;
 const MAX_RETRIES: u32 = 5;
 const BASE_BACKOFF_S: u64 = 1;
 
 pub struct Github {
+    host: String,
     cache: HashMap<(String, String), HashMap<u8, Vec<String>>>,
+    releases: HashMap<(String, String), HashMap<u8, Vec<Release>>>,
+    disk_cache: Cache,
     octo: Octocrab,
 }
 
+/// Tokens for GitHub hosts, read from `credentials.toml`
+#[derive(Deserialize, Default)]
+struct Credentials {
+    #[serde(default)]
+    github: HashMap<String, HostCredentials>,
+}
+
+#[derive(Deserialize)]
+struct HostCredentials {
+    token: String,
+}
+
 impl Github {
-    pub fn new(gh_token: Option<String>) -> Result<Self> {
-        let gh_token = if gh_token.is_some() {
-            gh_token
-        } else if env::var("GH_TOKEN").ok().is_some() {
-            env::var("GH_TOKEN").ok()
-        } else if env::var("GITHUB_TOKEN").ok().is_some() {
-            env::var("GITHUB_TOKEN").ok()
-        } else {
-            None
-        };
+    /// A client for the GitHub API at `host`, using `gh_token` or the token configured for the host
+    pub fn new(host: &str, gh_token: Option<String>, disk_cache: Cache) -> Result<Self> {
+        let gh_token = gh_token.or_else(|| token(host));
         if gh_token.is_none() {
-            debug!("No GitHub token was provided, you may see errors from rate limiting");
+            debug!(
+                "No GitHub token was provided for '{host}', you may see errors from rate limiting"
+            );
         }
-        Ok(match gh_token {
-            Some(token) => Self {
-                octo: Octocrab::builder().personal_token(token).build()?,
-                cache: Default::default(),
-            },
-            None => Self {
-                octo: Octocrab::default(),
-                cache: Default::default(),
+        let builder = if host == GITHUB_API {
+            Octocrab::builder()
+        } else {
+            Octocrab::builder()
+                .base_uri(host)
+                .context(format!("Invalid GitHub host '{host}'"))?
+        };
+        Ok(Self {
+            host: host.to_string(),
+            octo: match gh_token {
+                Some(token) => builder.personal_token(token).build()?,
+                None => builder.build()?,
             },
+            cache: Default::default(),
+            releases: Default::default(),
+            disk_cache,
         })
     }
 
     pub(crate) async fn print_rate_limit(&self) -> Result<()> {
-        println!(
-            "GitHub rate limits: '{:?}'",
+        info!(
+            "GitHub rate limits for '{}': '{:?}'",
+            self.host,
             self.octo.ratelimit().get().await?
         );
         Ok(())
@@ -61,6 +89,7 @@ impl Github {
             let res = self.tags_inner(org, project, offset, version_from).await;
             match res {
                 Ok(r) => return Ok(r),
+                Err(e) if self.disk_cache.offline() => return Err(e),
                 Err(e) => debug!("Failed to get tags: '{:?}'", e),
             }
             retry += 1;
@@ -84,9 +113,24 @@ impl Github {
                 return Ok(tags.clone());
             }
         }
-        let tags: Vec<String> = match version_from {
-            VersionFrom::Tag => self.get_tags(org, project, offset).await?,
-            VersionFrom::Branch => self.get_branches(org, project, offset).await?,
+        let key = format!("{} {org}/{project} {version_from:?} {offset}", self.host);
+        let tags: Vec<String> = match self.disk_cache.get("github", &key) {
+            Some(tags) => tags,
+            None => {
+                self.disk_cache.check_online("github", &key)?;
+                let tags = match version_from {
+                    VersionFrom::Tag => self.get_tags(org, project, offset).await?,
+                    VersionFrom::Branch => self.get_branches(org, project, offset).await?,
+                    VersionFrom::Release => self
+                        .releases(org, project, offset)
+                        .await?
+                        .into_iter()
+                        .map(|release| release.tag_name)
+                        .collect(),
+                };
+                self.disk_cache.set("github", &key, &tags);
+                tags
+            }
         };
         if let Some(cache) = self.cache.get_mut(&(org.to_string(), project.to_string())) {
             cache.insert(offset, tags.clone());
@@ -114,6 +158,67 @@ impl Github {
         .collect())
     }
 
+    /// A set of releases, newest first
+    async fn releases(&mut self, org: &str, project: &str, offset: u8) -> Result<Vec<Release>> {
+        let key = (org.to_string(), project.to_string());
+        if let Some(releases) = self.releases.get(&key).and_then(|sets| sets.get(&offset)) {
+            debug!("Using cached github releases for '{org}/{project}'");
+            return Ok(releases.clone());
+        }
+        let disk_key = format!("{} {org}/{project} releases {offset}", self.host);
+        if let Some(releases) = self.disk_cache.get::<Vec<Release>>("github", &disk_key) {
+            self.releases
+                .entry(key)
+                .or_default()
+                .insert(offset, releases.clone());
+            return Ok(releases);
+        }
+        self.disk_cache.check_online("github", &disk_key)?;
+        let mut retry = 0;
+        info!("Pulling releases from github for '{org}/{project}'");
+        while retry < MAX_RETRIES {
+            match self.get_releases(org, project, offset).await {
+                Ok(releases) => {
+                    self.disk_cache.set("github", &disk_key, &releases);
+                    self.releases
+                        .entry(key)
+                        .or_default()
+                        .insert(offset, releases.clone());
+                    return Ok(releases);
+                }
+                Err(e) => debug!("Failed to get releases: '{:?}'", e),
+            }
+            retry += 1;
+            debug!("Failed to reach github");
+            tokio::time::sleep(Duration::from_secs(BASE_BACKOFF_S * 2_u64.pow(retry))).await;
+        }
+        Err(anyhow!(
+            "Unable to pull releases for '{org}/{project}' after '{retry}' retries."
+        ))
+    }
+
+    async fn get_releases(&self, org: &str, project: &str, offset: u8) -> Result<Vec<Release>> {
+        let repo = self.octo.repos(org, project);
+        let releases = repo.releases();
+        Ok(
+            futures::future::join_all((MAX_PAGES * offset..MAX_PAGES + MAX_PAGES * offset).map(
+                |page| {
+                    releases
+                        .list()
+                        .per_page(RELEASES_PER_PAGE)
+                        .page(page as u32 + 1)
+                        .send()
+                },
+            ))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flat_map(|page| page.items)
+            .collect(),
+        )
+    }
+
     async fn get_branches(&self, org: &str, project: &str, offset: u8) -> Result<Vec<String>> {
         let repo = self.octo.repos(org, project);
         let num_pages = repo
@@ -142,19 +247,45 @@ impl Github {
         .collect())
     }
 
+    /// The tags a fetch version can pick from in a set of tags, branches or releases
+    async fn candidates(
+        &mut self,
+        project: &str,
+        fetch_version: &GithubFetchVersion,
+        offset: u8,
+    ) -> Result<Vec<String>> {
+        let org = &fetch_version.org;
+        match fetch_version.version_from {
+            VersionFrom::Release => Ok(self
+                .releases(org, project, offset)
+                .await?
+                .into_iter()
+                .filter(|release| fetch_version.includes(release))
+                .map(|release| release.tag_name)
+                .collect()),
+            _ => {
+                self.tags(org, project, offset, &fetch_version.version_from)
+                    .await
+            }
+        }
+    }
+
     pub(crate) async fn version(
         &mut self,
         target_version: &str,
-        org: &str,
         project: &str,
-        version_from: &VersionFrom,
+        fetch_version: &GithubFetchVersion,
     ) -> Result<String> {
+        let org = &fetch_version.org;
         let mut retry = 0;
         info!("Pulling tags from github for '{org}/{project}'");
         while retry < MAX_RETRIES {
             let res = find_tag(
                 target_version,
-                &self.tags(org, project, retry as u8, version_from).await?,
+                &fetch_version
+                    .filter
+                    .apply(&self.candidates(project, fetch_version, retry as u8).await?)?,
+                fetch_version.order,
             );
             match res {
                 Ok(r) => return Ok(r),
@@ -167,4 +298,133 @@ impl Github {
             "Unable to find tag for '{target_version}' in {org}/{project}"
         ))
     }
+
+    /// The template data of the release a version was picked from
+    pub(crate) async fn release(
+        &mut self,
+        project: &str,
+        fetch_version: &GithubFetchVersion,
+        version: &str,
+    ) -> Result<Value> {
+        let org = &fetch_version.org;
+        for offset in 0..MAX_RETRIES as u8 {
+            for release in self.releases(org, project, offset).await? {
+                if fetch_version.includes(&release)
+                    && fetch_version
+                        .filter
+                        .apply(std::slice::from_ref(&release.tag_name))?
+                        == [version]
+                {
+                    return Ok(release_data(&release));
+                }
+            }
+        }
+        Err(anyhow!(
+            "Unable to find the release of '{version}' in {org}/{project}"
+        ))
+    }
+}
+
+impl GithubFetchVersion {
+    /// Drafts and pre-releases are skipped unless they were asked for
+    fn includes(&self, release: &Release) -> bool {
+        (self.include_drafts || !release.draft) && (self.include_prereleases || !release.prerelease)
+    }
+}
+
+/// `{{release.tag}}`, `{{release.name}}`, `{{release.url}}` and `{{release.assets.<name>}}`
+fn release_data(release: &Release) -> Value {
+    json!({
+        "tag": release.tag_name,
+        "name": release.name.as_ref().unwrap_or(&release.tag_name),
+        "url": release.html_url.to_string(),
+        "assets": release
+            .assets
+            .iter()
+            .map(|asset| (asset.name.clone(), json!(asset.browser_download_url.to_string())))
+            .collect::<serde_json::Map<_, _>>(),
+    })
+}
+
+/// Release data for checking templates without calling GitHub, it has no assets
+pub(crate) fn sample_release(version: &str) -> Value {
+    json!({"tag": version, "name": version, "url": "", "assets": {}})
+}
+
+/// The token for a GitHub host.
+/// github.com uses `GH_TOKEN` or `GITHUB_TOKEN`, other hosts use `GITHUB_TOKEN_<HOST>`
+/// (e.g. `GITHUB_TOKEN_GITHUB_EXAMPLE_COM`) before the credentials file,
+/// and `GH_ENTERPRISE_TOKEN` or `GITHUB_ENTERPRISE_TOKEN` after it.
+fn token(host: &str) -> Option<String> {
+    let hostname = hostname(host);
+    let (before, after) = if host == GITHUB_API {
+        (
+            vec!["GH_TOKEN".to_string(), "GITHUB_TOKEN".to_string()],
+            vec![],
+        )
+    } else {
+        (
+            vec![format!(
+                "GITHUB_TOKEN_{}",
+                hostname
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() {
+                        c.to_ascii_uppercase()
+                    } else {
+                        '_'
+                    })
+                    .collect::<String>()
+            )],
+            vec!["GH_ENTERPRISE_TOKEN", "GITHUB_ENTERPRISE_TOKEN"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        )
+    };
+    before
+        .iter()
+        .find_map(|name| env::var(name).ok())
+        .or_else(|| {
+            credentials()
+                .github
+                .remove(&hostname)
+                .map(|host| host.token)
+        })
+        .or_else(|| after.iter().find_map(|name| env::var(name).ok()))
+}
+
+/// The name tokens are stored under, `github.com` for the public API
+fn hostname(host: &str) -> String {
+    if host == GITHUB_API {
+        return "github.com".to_string();
+    }
+    Url::parse(host)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_else(|| host.to_string())
+}
+
+/// `$TUXWRANGLER_CREDENTIALS` or `credentials.toml` in the tuxwrangler config directory
+fn credentials_path() -> Option<PathBuf> {
+    env::var_os("TUXWRANGLER_CREDENTIALS")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+                .map(|config| config.join("tuxwrangler").join("credentials.toml"))
+        })
+}
+
+fn credentials() -> Credentials {
+    let Some(path) = credentials_path().filter(|path| path.exists()) else {
+        return Credentials::default();
+    };
+    fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| Ok(toml::from_str(&text)?))
+        .unwrap_or_else(|e| {
+            warn!("Ignoring credentials file '{}': {e}", path.display());
+            Credentials::default()
+        })
 }
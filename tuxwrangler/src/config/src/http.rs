@@ -0,0 +1,121 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use log::{debug, info};
+use reqwest::Client;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use sha2::{Digest, Sha256};
+
+use crate::cache::Cache;
+
+const MAX_RETRIES: u32 = 5;
+const BASE_BACKOFF_S: u64 = 1;
+
+/// A client for reading versions from JSON documents
+pub struct Http {
+    client: Client,
+    cache: HashMap<String, Value>,
+    disk_cache: Cache,
+}
+
+impl Http {
+    pub fn new(disk_cache: Cache) -> Result<Self> {
+        Ok(Self {
+            client: Client::builder()
+                .user_agent(concat!("tuxwrangler/", env!("CARGO_PKG_VERSION")))
+                .build()?,
+            cache: Default::default(),
+            disk_cache,
+        })
+    }
+
+    /// Fetch a document and extract every version selected by the JSONPath, in document order
+    pub(crate) async fn versions(
+        &mut self,
+        url: &str,
+        json_path: &str,
+        headers: &HashMap<String, String>,
+    ) -> Result<Vec<String>> {
+        let path = JsonPath::parse(json_path).context(format!("Invalid JSONPath '{json_path}'"))?;
+        let document = self.document(url, headers).await?;
+        Ok(path
+            .query(&document)
+            .all()
+            .into_iter()
+            .flat_map(|node| match node {
+                // A path selecting a list of versions is flattened
+                Value::Array(values) => values.iter().collect(),
+                value => vec![value],
+            })
+            .filter_map(|value| match value {
+                Value::String(version) => Some(version.clone()),
+                Value::Number(version) => Some(version.to_string()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn document(&mut self, url: &str, headers: &HashMap<String, String>) -> Result<Value> {
+        let key = cache_key(url, headers);
+        if let Some(document) = self.cache.get(&key) {
+            debug!("Using cached document for '{url}'");
+            return Ok(document.clone());
+        }
+        if let Some(document) = self.disk_cache.get::<Value>("http", &key) {
+            self.cache.insert(key, document.clone());
+            return Ok(document);
+        }
+        self.disk_cache.check_online("http", &key)?;
+        info!("Pulling versions from '{url}'");
+        let mut retry = 0;
+        while retry < MAX_RETRIES {
+            match self.document_inner(url, headers).await {
+                Ok(document) => {
+                    self.disk_cache.set("http", &key, &document);
+                    self.cache.insert(key, document.clone());
+                    return Ok(document);
+                }
+                Err(e) => debug!("Failed to get document: '{:?}'", e),
+            }
+            retry += 1;
+            debug!("Failed to reach '{url}'");
+            tokio::time::sleep(Duration::from_secs(BASE_BACKOFF_S * 2_u64.pow(retry))).await;
+        }
+        Err(anyhow!(
+            "Unable to pull versions from '{url}' after '{retry}' retries."
+        ))
+    }
+
+    async fn document_inner(&self, url: &str, headers: &HashMap<String, String>) -> Result<Value> {
+        headers
+            .iter()
+            .fold(self.client.get(url), |request, (name, value)| {
+                request.header(name, value)
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context(format!("'{url}' did not return a JSON document"))
+    }
+}
+
+/// The cache key of a document, which differs for each set of headers.
+/// Headers are hashed so that tokens are never written to the cache.
+fn cache_key(url: &str, headers: &HashMap<String, String>) -> String {
+    if headers.is_empty() {
+        return url.to_string();
+    }
+    let headers = headers
+        .iter()
+        .map(|(name, value)| format!("{}: {value}", name.to_lowercase()))
+        .sorted()
+        .join("\n");
+    format!(
+        "{url} headers sha256:{:x}",
+        Sha256::digest(headers.as_bytes())
+    )
+}
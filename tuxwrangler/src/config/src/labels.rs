@@ -0,0 +1,121 @@
+use std::{collections::BTreeMap, path::Path, process::Command};
+
+use chrono::{SecondsFormat, Utc};
+use itertools::Itertools;
+use log::debug;
+
+use crate::{
+    lock::{ImageIdentifier, SingleBuild},
+    TuxWranglerConfigLocked,
+};
+
+/// Where and when images are built, stamped on every built image.
+/// Written Dockerfiles use the default, without any, so they are the same for the same lock file.
+#[derive(Default)]
+pub(crate) struct Provenance {
+    /// When the images were built (RFC 3339)
+    created: Option<String>,
+    /// The commit checked out in the directory the images are built in
+    revision: Option<String>,
+}
+
+impl Provenance {
+    pub(crate) fn new(home: &Path) -> Self {
+        let revision = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(home)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+        if revision.is_none() {
+            debug!(
+                "'{}' is not a git repository, images have no revision",
+                home.display()
+            );
+        }
+        Self {
+            created: Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+            revision,
+        }
+    }
+}
+
+impl TuxWranglerConfigLocked {
+    /// The labels of the image built for a build and platform.
+    /// Labels from the config replace the generated ones.
+    pub(crate) fn labels(
+        &self,
+        build: &SingleBuild,
+        platform: Option<&str>,
+        provenance: &Provenance,
+    ) -> BTreeMap<String, String> {
+        let mut labels = BTreeMap::from([(oci("version"), build.image_tag.clone())]);
+        if let Some(created) = &provenance.created {
+            labels.insert(oci("created"), created.clone());
+        }
+        if let Some(revision) = &provenance.revision {
+            labels.insert(oci("revision"), revision.clone());
+        }
+        if let Some(base) = self.base(&build.base) {
+            let digest = match &base.identifier {
+                ImageIdentifier::Digest { digest } => Some(digest),
+                ImageIdentifier::Tag { .. } => None,
+            };
+            if let Some(digest) = platform
+                .and_then(|platform| base.platforms.get(platform))
+                .or(digest)
+            {
+                labels.insert(oci("base.digest"), digest.clone());
+            }
+            labels.insert(
+                oci("base.name"),
+                format!("{}{}", base.registry, base.identifier),
+            );
+        }
+        for feature in &build.features {
+            labels.insert(
+                format!("io.tuxwrangler.feature.{}", feature.name),
+                feature.version.clone(),
+            );
+        }
+        if let Some(config_hash) = &self.config_hash {
+            labels.insert(
+                "io.tuxwrangler.config-hash".to_string(),
+                config_hash.clone(),
+            );
+        }
+        labels.extend(build.labels.clone());
+        labels
+    }
+}
+
+/// A `LABEL` instruction, quoting the labels so that they are used as they are
+pub(crate) fn label_instruction(labels: &BTreeMap<String, String>) -> Option<String> {
+    if labels.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "LABEL {}",
+        labels
+            .iter()
+            .map(|(name, value)| format!("{}={}", quote(name), quote(value)))
+            .join(" \\\n      ")
+    ))
+}
+
+fn oci(name: &str) -> String {
+    format!("org.opencontainers.image.{name}")
+}
+
+/// Quote a value for a Dockerfile, without variable substitution
+fn quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('$', "\\$")
+            .replace('\n', " ")
+    )
+}
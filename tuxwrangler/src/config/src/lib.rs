@@ -1,42 +1,112 @@
+mod cache;
+mod changelog;
+mod check;
+mod command;
 pub mod config;
+mod constraint;
+pub mod diff;
 pub mod docker;
 mod docker_build;
 mod docker_file;
+mod docker_push;
 mod docker_run;
 mod docker_version;
+mod filter;
+mod git;
 mod github;
+mod http;
+mod labels;
 pub mod lock;
+mod platform;
+mod registry;
+mod tags;
+#[cfg(test)]
+mod test_lock;
 mod update;
+mod validate;
 mod version;
 
 use std::{
+    collections::{hash_map::Entry, HashMap},
     fs,
     path::{Path, PathBuf},
 };
 
 use anyhow::Context;
+pub use cache::{Cache, CacheEntry, DEFAULT_TTL};
 pub use config::TuxWranglerConfig;
+use diff::LockChanges;
 use docker::Docker;
+pub use docker_build::BuildOptions;
 use docker_file::create_dockerfile;
-use github::Github;
+pub use filter::BuildFilter;
+use git::Git;
+use github::{Github, GITHUB_API};
+use http::Http;
+use labels::Provenance;
 pub use lock::TuxWranglerConfigLocked;
+use registry::Registry;
+use update::Pins;
+pub use validate::ConfigError;
 
 pub type Result<T> = anyhow::Result<T>;
+
+/// The version of tuxwrangler, recorded in every lock file
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub struct Clients {
     pub docker: Docker,
-    pub gh: Github,
+    /// A GitHub client for each API host, github.com is always present
+    pub gh: HashMap<String, Github>,
+    pub registry: Registry,
+    pub http: Http,
+    pub git: Git,
+    /// Responses of version sources that are kept between runs
+    pub cache: Cache,
 }
 
 impl Clients {
-    pub fn new(gh_token: Option<String>) -> Result<Self> {
+    pub fn new(gh_token: Option<String>, cache: Cache) -> Result<Self> {
         Ok(Self {
             docker: Docker::new(".".into())?,
-            gh: Github::new(gh_token)?,
+            gh: HashMap::from([(
+                GITHUB_API.to_string(),
+                Github::new(GITHUB_API, gh_token, cache.clone())?,
+            )]),
+            registry: Registry::new(cache.clone())?,
+            http: Http::new(cache.clone())?,
+            git: Git::new(cache.clone()),
+            cache,
+        })
+    }
+
+    /// The client for a GitHub API host, github.com when there is no host
+    pub(crate) fn github(&mut self, host: Option<&str>) -> Result<&mut Github> {
+        let host = host.unwrap_or(GITHUB_API).trim_end_matches('/').to_string();
+        Ok(match self.gh.entry(host) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let github = Github::new(entry.key(), None, self.cache.clone())?;
+                entry.insert(github)
+            }
         })
     }
 
+    /// The digest of an image, from the cache or the registry
+    pub(crate) async fn digest(&self, image: &str) -> Result<String> {
+        if let Some(digest) = self.cache.get("digest", image) {
+            return Ok(digest);
+        }
+        self.cache.check_online("digest", image)?;
+        let digest = self.docker.digest(image).await?;
+        self.cache.set("digest", image, &digest);
+        Ok(digest)
+    }
+
     pub async fn print_gh_rate_limit(&self) -> Result<()> {
-        self.gh.print_rate_limit().await?;
+        for gh in self.gh.values() {
+            gh.print_rate_limit().await?;
+        }
         Ok(())
     }
 }
@@ -57,29 +127,89 @@ pub fn load_config(path: PathBuf) -> Result<TuxWranglerConfig> {
     .context("Unable to serialize config file")
 }
 
+/// Compute the changes between 2 lock files
+pub fn lock_changes(old_path: PathBuf, new_path: PathBuf) -> Result<LockChanges> {
+    Ok(load_lockfile(old_path)?.update_changes(load_lockfile(new_path)?))
+}
+
+/// Check whether the lock file is up to date with the config without making any network calls.
+/// Returns every reason the lock file is out of date.
+pub fn check_lock(config_path: PathBuf, lock_path: PathBuf) -> Result<Vec<String>> {
+    load_config(config_path)?.stale_reasons(&load_lockfile(lock_path)?)
+}
+
+/// Validate the config without making any network calls.
+/// Returns every error found along with its location in the file.
+pub fn validate_config(path: PathBuf) -> Result<Vec<ConfigError>> {
+    Ok(validate::validate(&fs::read_to_string(&path).context(
+        format!("Unable to open config file at '{}'", path.display()),
+    )?))
+}
+
+/// Resolve the config into a lock file.
+/// When `only` names bases or features (`name` or `name:version`), only those are resolved again
+/// and every other entry is kept as it is in the existing lock file.
+/// When the cache is offline, entries that aren't cached are taken from the existing lock file.
 pub async fn update_lock(
     clients: &mut Clients,
     config_path: PathBuf,
     lock_path: PathBuf,
+    only: &[String],
 ) -> Result<()> {
-    load_config(config_path)?
-        .build_locked(clients)
-        .await?
-        .write(lock_path)
+    let config = load_config(config_path)?;
+    let offline = clients.cache.offline() && lock_path.exists();
+    let previous = if !only.is_empty() || offline {
+        Some(load_lockfile(lock_path.clone())?)
+    } else {
+        None
+    };
+    let pins = previous
+        .as_ref()
+        .filter(|_| !only.is_empty())
+        .map(|previous| Pins::new(previous, only));
+    let locked = config
+        .build_locked(
+            clients,
+            pins.as_ref(),
+            previous.as_ref().filter(|_| offline),
+        )
+        .await?;
+    locked.write(lock_path)
 }
 
 pub async fn build_images(
-    clients: &Clients,
+    clients: &mut Clients,
+    locked: TuxWranglerConfigLocked,
+    options: &BuildOptions,
+) -> Result<()> {
+    locked
+        .build_images(&clients.docker, &mut clients.registry, options)
+        .await
+}
+
+pub async fn push_images(
+    clients: &mut Clients,
     locked: TuxWranglerConfigLocked,
-    skip_tags: bool,
+    jobs: usize,
 ) -> Result<()> {
-    locked.build_images(&clients.docker, skip_tags).await
+    locked
+        .push_images(&clients.docker, &mut clients.registry, jobs)
+        .await
+}
+
+/// The number of concurrent jobs to run when none is given, the number of CPUs
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(usize::from)
+        .unwrap_or(1)
 }
 
 pub fn write_dockerfile(locked: TuxWranglerConfigLocked, out_dir: &Path) -> Result<()> {
     fs::write(
         out_dir.join("Dockerfile"),
-        create_dockerfile(&locked)?.0.join("\n"),
+        create_dockerfile(&locked, &Provenance::default())?
+            .0
+            .join("\n"),
     )?;
 
     Ok(())
@@ -2,7 +2,13 @@ use crate::Result;
 use anyhow::Context;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display, fs::File, io::Write, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    fs::File,
+    io::Write,
+    path::PathBuf,
+};
 use toml_edit::DocumentMut;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -10,6 +16,14 @@ pub struct TuxWranglerConfigLocked {
     /// The docker registry that images should be pushed to.
     pub registry: String,
 
+    /// A hash of the normalized config this lock file was created from
+    #[serde(default)]
+    pub config_hash: Option<String>,
+
+    /// The version of tuxwrangler that created this lock file
+    #[serde(default)]
+    pub tool_version: Option<String>,
+
     /// All versions for the supported bases
     #[serde(rename = "base", default)]
     pub bases: Vec<BaseConfig>,
@@ -21,6 +35,14 @@ pub struct TuxWranglerConfigLocked {
     /// The abstract builds that should be run for this configuration
     #[serde(rename = "build", default)]
     pub builds: Vec<SingleBuild>,
+
+    /// Where the build arguments used by feature steps are read from
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub build_args: BTreeMap<String, BuildArg>,
+
+    /// Where the secrets used by feature steps are read from, their values are never locked
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, Secret>,
 }
 
 impl TuxWranglerConfigLocked {
@@ -30,6 +52,34 @@ impl TuxWranglerConfigLocked {
             .find(|base| base.name == target_base.name && base.version == target_base.version)
     }
 
+    /// The locked base a target version of the config resolved to
+    pub fn resolved_base(&self, name: &str, target_version: &str) -> Option<&BaseConfig> {
+        self.bases.iter().find(|base| {
+            base.name == name && base.target_version.as_deref() == Some(target_version)
+        })
+    }
+
+    /// The actual version a target version of a base or feature resolved to
+    pub fn resolved_version(&self, name: &str, target_version: &str) -> Option<&str> {
+        self.resolved_base(name, target_version)
+            .map(|base| base.version.as_str())
+            .or_else(|| {
+                self.resolved_feature(name, target_version)
+                    .map(|feature| feature.version.as_str())
+            })
+    }
+
+    /// The locked feature a target version of the config resolved to
+    pub fn resolved_feature(
+        &self,
+        name: &str,
+        target_version: &str,
+    ) -> Option<&InstallationConfig> {
+        self.features.iter().find(|feature| {
+            feature.name == name && feature.target_version.as_deref() == Some(target_version)
+        })
+    }
+
     pub fn package_manager_for_base(&self, base: &SingleVersioned) -> Option<String> {
         self.base(base).map(|p| p.package_manager.to_string())
     }
@@ -79,13 +129,19 @@ impl Display for SingleVersioned {
 pub struct BaseConfig {
     pub name: String,
     pub version: String,
+    /// The version from the config that resolved to `version`
+    #[serde(default)]
+    pub target_version: Option<String>,
     pub registry: String,
     pub identifier: ImageIdentifier,
     pub package_manager: String,
     pub tag: Option<String>,
+    /// The digest of the image for each platform the base is built for
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub platforms: BTreeMap<String, String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(tag = "type")]
 pub enum ImageIdentifier {
     Tag { tag: String },
@@ -105,6 +161,9 @@ impl Display for ImageIdentifier {
 pub struct InstallationConfig {
     pub name: String,
     pub version: String,
+    /// The version from the config that resolved to `version`
+    #[serde(default)]
+    pub target_version: Option<String>,
     #[serde(rename = "step")]
     pub steps: Vec<Layer>,
     pub tag: Option<String>,
@@ -127,6 +186,35 @@ pub struct Layer {
     pub installation: Installation,
     #[serde(default)]
     pub copy: HashMap<String, String>,
+    /// The build arguments declared (`ARG`) for this step
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub build_args: Vec<String>,
+    /// The secrets mounted into every `RUN` of this step at `/run/secrets/<name>`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
+}
+
+/// Where the value of a build argument is read from when building
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BuildArg {
+    /// An environment variable
+    Env(String),
+    /// The content of a file, without trailing newlines
+    File(PathBuf),
+    /// A literal value
+    Value(String),
+}
+
+/// Where a BuildKit secret is read from when building.
+/// There are no literal secrets since they would be written to the lock file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Secret {
+    /// An environment variable
+    Env(String),
+    /// A file
+    File(PathBuf),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -165,6 +253,44 @@ pub struct SingleBuild {
     pub target: String,
     pub image_name: String,
     pub image_tag: String,
+    #[serde(default)]
+    pub alias_tags: Vec<String>,
+    /// The platforms an image is built for, only the platform of the Docker daemon when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub platforms: Vec<String>,
+    /// Labels from the config, added to the labels every image receives
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl SingleBuild {
+    /// All tags this build should receive, starting with `image_tag`
+    pub fn tags(&self) -> Vec<String> {
+        vec![self.image_tag.clone()]
+            .into_iter()
+            .chain(self.alias_tags.iter().cloned())
+            .unique()
+            .collect()
+    }
+
+    /// The platforms this build is built for, `None` being the platform of the Docker daemon
+    pub fn platforms(&self) -> Vec<Option<&str>> {
+        if self.platforms.is_empty() {
+            vec![None]
+        } else {
+            self.platforms.iter().map(|p| Some(p.as_str())).collect()
+        }
+    }
+
+    /// The repository this build is pushed to in the given registry
+    pub fn repository(&self, registry: &str) -> String {
+        let registry = registry.trim_end_matches('/');
+        if registry.is_empty() {
+            self.image_name.clone()
+        } else {
+            format!("{registry}/{}", self.image_name)
+        }
+    }
 }
 
 impl Display for SingleBuild {
@@ -0,0 +1,61 @@
+use std::fmt::Display;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// The platform of an image (`os/architecture[/variant]`), as it appears in manifest lists
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Platform {
+    pub(crate) architecture: String,
+    pub(crate) os: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) variant: Option<String>,
+}
+
+impl Platform {
+    pub(crate) fn parse(platform: &str) -> Result<Self> {
+        let parts = platform.split('/').collect::<Vec<_>>();
+        let valid = |part: &&str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        };
+        match parts[..] {
+            [os, architecture] | [os, architecture, _] if parts.iter().all(valid) => Ok(Self {
+                os: os.to_string(),
+                architecture: architecture.to_string(),
+                variant: parts.get(2).map(|variant| variant.to_string()),
+            }),
+            _ => Err(anyhow!(
+                "Invalid platform '{platform}', expected `os/architecture[/variant]` like 'linux/arm64'"
+            )),
+        }
+    }
+
+    /// Whether an image for `other` can be used for this platform.
+    /// A platform without a variant accepts any variant, like `linux/arm64` accepts `linux/arm64/v8`.
+    pub(crate) fn accepts(&self, other: &Platform) -> bool {
+        self.os == other.os
+            && self.architecture == other.architecture
+            && (self.variant.is_none() || self.variant == other.variant)
+    }
+
+    /// The tag of the image built for a platform, the tag itself for the platform of the Docker daemon
+    pub(crate) fn tag(tag: &str, platform: Option<&str>) -> String {
+        match platform {
+            Some(platform) => format!("{tag}-{}", platform.replace('/', "-")),
+            None => tag.to_string(),
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.os, self.architecture)?;
+        if let Some(variant) = &self.variant {
+            write!(f, "/{variant}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,420 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use log::{debug, info};
+use regex::Regex;
+use reqwest::{header, Client, Method, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{cache::Cache, docker::Docker, platform::Platform};
+
+const MAX_RETRIES: u32 = 5;
+const BASE_BACKOFF_S: u64 = 1;
+const PAGE_SIZE: u32 = 1000;
+const DOCKER_HUB: &str = "registry-1.docker.io";
+/// Docker Hub credentials are stored under the legacy index address
+const DOCKER_HUB_CREDENTIALS: &str = "https://index.docker.io/v1/";
+const OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+const DOCKER_MANIFEST_LIST: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+const DOCKER_MANIFEST: &str = "application/vnd.docker.distribution.manifest.v2+json";
+/// Every manifest type that is accepted when fetching a manifest
+const MANIFEST_TYPES: [&str; 4] = [
+    OCI_INDEX,
+    DOCKER_MANIFEST_LIST,
+    "application/vnd.oci.image.manifest.v1+json",
+    DOCKER_MANIFEST,
+];
+
+/// A client for the tag list API of OCI distribution (Docker v2) registries
+pub struct Registry {
+    client: Client,
+    cache: HashMap<String, Vec<String>>,
+    /// Bearer tokens for each repository
+    tokens: HashMap<String, String>,
+    disk_cache: Cache,
+}
+
+#[derive(Deserialize)]
+struct TagList {
+    tags: Option<Vec<String>>,
+}
+
+/// A reference to a manifest, as listed in a manifest list
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: String,
+    size: usize,
+    digest: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    platform: Option<Platform>,
+}
+
+/// A manifest list (OCI image index), single platform manifests have no `manifests`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestList {
+    schema_version: u8,
+    media_type: Option<String>,
+    manifests: Option<Vec<Descriptor>>,
+}
+
+#[derive(Deserialize)]
+struct Token {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// A repository on a registry
+pub(crate) struct Repository {
+    pub(crate) host: String,
+    pub(crate) name: String,
+}
+
+impl Repository {
+    /// Split an image reference (`eclipse-temurin`, `ghcr.io/org/app:tag`) into its registry and repository name
+    pub(crate) fn parse(image: &str) -> Self {
+        let image = image.split('@').next().unwrap_or(image);
+        let (host, name) = match image.split_once('/') {
+            Some((host, name))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host.to_string(), name.to_string())
+            }
+            Some(_) => (DOCKER_HUB.to_string(), image.to_string()),
+            None => (DOCKER_HUB.to_string(), format!("library/{image}")),
+        };
+        // Drop the tag, ports only appear in the host
+        let name = name.split(':').next().unwrap_or(&name).to_string();
+        Self { host, name }
+    }
+
+    /// Registries on the local machine are reached over http, like the Docker daemon does
+    pub(crate) fn base_url(&self) -> String {
+        let local = ["localhost", "127.0.0.1"]
+            .iter()
+            .any(|local| self.host.split(':').next() == Some(local));
+        format!("{}://{}", if local { "http" } else { "https" }, self.host)
+    }
+
+    fn credentials_key(&self) -> &str {
+        if self.host == DOCKER_HUB {
+            DOCKER_HUB_CREDENTIALS
+        } else {
+            &self.host
+        }
+    }
+}
+
+impl Registry {
+    pub fn new(disk_cache: Cache) -> Result<Self> {
+        Ok(Self {
+            client: Client::builder()
+                .user_agent(concat!("tuxwrangler/", env!("CARGO_PKG_VERSION")))
+                .build()?,
+            cache: Default::default(),
+            tokens: Default::default(),
+            disk_cache,
+        })
+    }
+
+    /// List every tag of an image's repository
+    pub(crate) async fn tags(&mut self, image: &str) -> Result<Vec<String>> {
+        let repository = Repository::parse(image);
+        let key = format!("{}/{}", repository.host, repository.name);
+        if let Some(tags) = self.cache.get(&key) {
+            debug!("Using cached registry tags for '{key}'");
+            return Ok(tags.clone());
+        }
+        if let Some(tags) = self.disk_cache.get::<Vec<String>>("registry", &key) {
+            self.cache.insert(key, tags.clone());
+            return Ok(tags);
+        }
+        self.disk_cache.check_online("registry", &key)?;
+        info!("Pulling tags from registry for '{key}'");
+        let mut retry = 0;
+        while retry < MAX_RETRIES {
+            match self.tags_inner(&repository).await {
+                Ok(tags) => {
+                    self.disk_cache.set("registry", &key, &tags);
+                    self.cache.insert(key, tags.clone());
+                    return Ok(tags);
+                }
+                Err(e) => debug!("Failed to get tags: '{:?}'", e),
+            }
+            retry += 1;
+            debug!("Failed to reach registry '{}'", repository.host);
+            tokio::time::sleep(Duration::from_secs(BASE_BACKOFF_S * 2_u64.pow(retry))).await;
+        }
+        Err(anyhow!(
+            "Unable to pull tags for '{key}' after '{retry}' retries."
+        ))
+    }
+
+    async fn tags_inner(&mut self, repository: &Repository) -> Result<Vec<String>> {
+        let base_url = repository.base_url();
+        let mut url = format!("{base_url}/v2/{}/tags/list?n={PAGE_SIZE}", repository.name);
+        let mut tags = Vec::new();
+        loop {
+            let response = self.get(repository, &url).await?;
+            let next = next_link(&response);
+            tags.extend(
+                response
+                    .json::<TagList>()
+                    .await
+                    .context("Unable to parse the tag list")?
+                    .tags
+                    .unwrap_or_default(),
+            );
+            match next {
+                Some(next) if next.starts_with('/') => url = format!("{base_url}{next}"),
+                Some(next) => url = next,
+                None => return Ok(tags),
+            }
+        }
+    }
+
+    /// The digest of the manifest for each platform in the manifest list of an image digest
+    pub(crate) async fn platform_digests(
+        &mut self,
+        image: &str,
+        digest: &str,
+        platforms: &[String],
+    ) -> Result<BTreeMap<String, String>> {
+        let repository = Repository::parse(image);
+        let key = format!("{}/{}@{digest}", repository.host, repository.name);
+        let manifests = match self.disk_cache.get::<Vec<Descriptor>>("manifest", &key) {
+            Some(manifests) => manifests,
+            None => {
+                self.disk_cache.check_online("manifest", &key)?;
+                info!("Fetching the manifest list of '{key}'");
+                let mut retry = 0;
+                loop {
+                    match self.manifest(&repository, digest).await {
+                        Ok((_, body)) => {
+                            let manifests = serde_json::from_slice::<ManifestList>(&body)
+                                .context(format!("Unable to parse the manifest of '{key}'"))?
+                                .manifests
+                                .context(format!(
+                                    "'{image}' is not a multi-platform image, it has no manifest list"
+                                ))?;
+                            self.disk_cache.set("manifest", &key, &manifests);
+                            break manifests;
+                        }
+                        Err(e) => debug!("Failed to get manifest: '{:?}'", e),
+                    }
+                    retry += 1;
+                    if retry == MAX_RETRIES {
+                        return Err(anyhow!(
+                            "Unable to fetch the manifest of '{key}' after '{retry}' retries."
+                        ));
+                    }
+                    tokio::time::sleep(Duration::from_secs(BASE_BACKOFF_S * 2_u64.pow(retry)))
+                        .await;
+                }
+            }
+        };
+        platforms
+            .iter()
+            .map(|platform| {
+                let wanted = Platform::parse(platform)?;
+                manifests
+                    .iter()
+                    .find(|m| m.platform.as_ref().is_some_and(|p| wanted.accepts(p)))
+                    .map(|m| (platform.clone(), m.digest.clone()))
+                    .context(format!(
+                        "'{image}' is not available for '{platform}', it provides: {}",
+                        manifests
+                            .iter()
+                            .filter_map(|m| m.platform.as_ref())
+                            // Attestations are listed with an unknown platform
+                            .filter(|p| p.os != "unknown")
+                            .join(", ")
+                    ))
+            })
+            .collect()
+    }
+
+    /// Combine the images pushed for each platform into a manifest list and push it as `tag`.
+    /// `images` holds the platform and the tag of each pushed image.
+    pub(crate) async fn push_manifest_list(
+        &mut self,
+        repo: &str,
+        tag: &str,
+        images: &[(String, String)],
+    ) -> Result<()> {
+        let repository = Repository::parse(repo);
+        let mut manifests = Vec::new();
+        for (platform, image_tag) in images {
+            let wanted = Platform::parse(platform)?;
+            let (media_type, body) = self.manifest(&repository, image_tag).await?;
+            let list = serde_json::from_slice::<ManifestList>(&body).context(format!(
+                "Unable to parse the manifest of '{repo}:{image_tag}'"
+            ))?;
+            manifests.push(match list.manifests {
+                // Image stores that add attestations push a manifest list for a single platform
+                Some(list) => list
+                    .into_iter()
+                    .find(|m| m.platform.as_ref().is_some_and(|p| wanted.accepts(p)))
+                    .context(format!(
+                        "'{repo}:{image_tag}' has no image for '{platform}'"
+                    ))?,
+                None => Descriptor {
+                    media_type,
+                    size: body.len(),
+                    digest: format!("sha256:{:x}", Sha256::digest(&body)),
+                    platform: Some(wanted),
+                },
+            });
+        }
+        let media_type = if manifests.iter().all(|m| m.media_type == DOCKER_MANIFEST) {
+            DOCKER_MANIFEST_LIST
+        } else {
+            OCI_INDEX
+        };
+        let body = serde_json::to_vec(&ManifestList {
+            schema_version: 2,
+            media_type: Some(media_type.to_string()),
+            manifests: Some(manifests),
+        })?;
+        let url = format!(
+            "{}/v2/{}/manifests/{tag}",
+            repository.base_url(),
+            repository.name
+        );
+        debug!("Pushing manifest list '{repo}:{tag}'");
+        self.send(&repository, Method::PUT, &url, Some((media_type, body)))
+            .await
+            .context(format!("Unable to push manifest list '{repo}:{tag}'"))?;
+        Ok(())
+    }
+
+    /// Fetch a manifest by tag or digest, returning its media type and content
+    async fn manifest(
+        &mut self,
+        repository: &Repository,
+        reference: &str,
+    ) -> Result<(String, Vec<u8>)> {
+        let url = format!(
+            "{}/v2/{}/manifests/{reference}",
+            repository.base_url(),
+            repository.name
+        );
+        let response = self.send(repository, Method::GET, &url, None).await?;
+        let media_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or(DOCKER_MANIFEST)
+            .to_string();
+        Ok((media_type, response.bytes().await?.to_vec()))
+    }
+
+    /// Send an authenticated GET request, requesting a token when the registry asks for one
+    pub(crate) async fn get(&mut self, repository: &Repository, url: &str) -> Result<Response> {
+        self.send(repository, Method::GET, url, None).await
+    }
+
+    /// Send an authenticated request with an optional body and its content type
+    async fn send(
+        &mut self,
+        repository: &Repository,
+        method: Method,
+        url: &str,
+        body: Option<(&str, Vec<u8>)>,
+    ) -> Result<Response> {
+        let key = format!("{}/{}", repository.host, repository.name);
+        let client = self.client.clone();
+        let request = |token: Option<&String>| {
+            let request = client
+                .request(method.clone(), url)
+                .header(header::ACCEPT, MANIFEST_TYPES.join(", "));
+            let request = match &body {
+                Some((content_type, body)) => request
+                    .header(header::CONTENT_TYPE, *content_type)
+                    .body(body.clone()),
+                None => request,
+            };
+            match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        };
+        let response = request(self.tokens.get(&key)).send().await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response.error_for_status()?);
+        }
+        let challenge = response
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let response = if challenge.to_lowercase().starts_with("bearer") {
+            let token = self.token(repository, &challenge).await?;
+            self.tokens.insert(key, token.clone());
+            request(Some(&token)).send().await?
+        } else {
+            basic_auth(request(None), repository).send().await?
+        };
+        Ok(response.error_for_status()?)
+    }
+
+    /// Fetch a bearer token from the realm of a `WWW-Authenticate` challenge
+    async fn token(&self, repository: &Repository, challenge: &str) -> Result<String> {
+        let re = Regex::new(r#"(\w+)="([^"]*)""#).expect("regex");
+        let params = re
+            .captures_iter(challenge)
+            .map(|c| (c[1].to_lowercase(), c[2].to_string()))
+            .collect::<HashMap<_, _>>();
+        let realm = params.get("realm").context(format!(
+            "No realm in authentication challenge '{challenge}'"
+        ))?;
+        let scope = params
+            .get("scope")
+            .cloned()
+            .unwrap_or_else(|| format!("repository:{}:pull", repository.name));
+        let query = params
+            .get("service")
+            .map(|service| vec![("service", service.clone())])
+            .unwrap_or_default()
+            .into_iter()
+            .chain([("scope", scope)])
+            .collect::<Vec<_>>();
+        let token = basic_auth(self.client.get(realm).query(&query), repository)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Token>()
+            .await
+            .context("Unable to parse the registry token")?;
+        token
+            .token
+            .or(token.access_token)
+            .context(format!("No token was returned by '{realm}'"))
+    }
+}
+
+/// Add the stored Docker credentials for the registry, if there are any
+fn basic_auth(request: RequestBuilder, repository: &Repository) -> RequestBuilder {
+    match Docker::credentials(repository.credentials_key()) {
+        Some(credentials) => request.basic_auth(
+            credentials.username.unwrap_or_default(),
+            credentials.password,
+        ),
+        None => request,
+    }
+}
+
+/// The next page from a `Link: </v2/...>; rel="next"` header
+fn next_link(response: &Response) -> Option<String> {
+    let link = response.headers().get(header::LINK)?.to_str().ok()?;
+    link.split(',')
+        .find(|l| l.contains("rel=\"next\""))
+        .and_then(|l| Some(l.split_once('<')?.1.split_once('>')?.0.to_string()))
+}
@@ -0,0 +1,189 @@
+use anyhow::Context;
+use log::debug;
+use regex::Regex;
+
+use crate::{
+    config::{FetchVersion, TagFilter},
+    Result,
+};
+
+/// Matches the common markers of a pre-release (`1.2.3-rc1`, `2.0.0-beta.2`, `nightly-2024-01-01`, `4.0.0-M1`)
+const PRERELEASE: &str = r"(?i)(^|[^a-z])(alpha|beta|rc|pre|preview|snapshot|nightly|dev|ea|milestone|m\d+)\d*([^a-z]|$)";
+
+fn regexes(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).context(format!("Invalid tag regex '{p}'")))
+        .collect()
+}
+
+impl TagFilter {
+    /// Make sure every regex in the filter compiles
+    pub(crate) fn check(&self) -> Result<()> {
+        regexes(&self.include)?;
+        regexes(&self.exclude)?;
+        self.capture_regex()?;
+        Ok(())
+    }
+
+    fn capture_regex(&self) -> Result<Option<Regex>> {
+        self.capture
+            .as_ref()
+            .map(|p| Regex::new(p).context(format!("Invalid capture regex '{p}'")))
+            .transpose()
+    }
+
+    /// Remove the tags that should not be used as a version and normalize the rest, keeping their order
+    pub(crate) fn apply(&self, tags: &[String]) -> Result<Vec<String>> {
+        let include = regexes(&self.include)?;
+        let exclude = regexes(&self.exclude)?;
+        let capture = self.capture_regex()?;
+        let prerelease = Regex::new(PRERELEASE).expect("regex");
+        let filtered = tags
+            .iter()
+            .filter(|tag| include.is_empty() || include.iter().any(|re| re.is_match(tag)))
+            .filter(|tag| !exclude.iter().any(|re| re.is_match(tag)))
+            .filter_map(|tag| {
+                let tag = self
+                    .strip_prefix
+                    .as_ref()
+                    .and_then(|prefix| tag.strip_prefix(prefix.as_str()))
+                    .unwrap_or(tag);
+                match &capture {
+                    Some(capture) => capture.captures(tag).and_then(|c| {
+                        c.name("version")
+                            .or_else(|| c.get(1))
+                            .map(|m| m.as_str().to_string())
+                    }),
+                    None => Some(tag.to_string()),
+                }
+            })
+            .filter(|tag| self.prerelease || !prerelease.is_match(tag))
+            .collect::<Vec<_>>();
+        if filtered.len() != tags.len() {
+            debug!(
+                "{} of {} tags remain after filtering",
+                filtered.len(),
+                tags.len()
+            );
+        }
+        Ok(filtered)
+    }
+}
+
+impl FetchVersion {
+    pub(crate) fn filter(&self) -> &TagFilter {
+        match self {
+            FetchVersion::Docker(fetch_version) => &fetch_version.filter,
+            FetchVersion::Github(fetch_version) => &fetch_version.filter,
+            FetchVersion::Registry(fetch_version) => &fetch_version.filter,
+            FetchVersion::Http(fetch_version) => &fetch_version.filter,
+            FetchVersion::Command(fetch_version) => &fetch_version.filter,
+            FetchVersion::Git(fetch_version) => &fetch_version.filter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> TagFilter {
+        TagFilter {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            prerelease: true,
+            strip_prefix: None,
+            capture: None,
+        }
+    }
+
+    fn apply(filter: &TagFilter, tags: &[&str]) -> Vec<String> {
+        filter
+            .apply(&tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>())
+            .unwrap()
+    }
+
+    #[test]
+    fn keeps_every_tag_by_default() {
+        assert_eq!(apply(&filter(), &["v1.0", "1.1-rc1"]), ["v1.0", "1.1-rc1"]);
+    }
+
+    #[test]
+    fn includes_and_excludes_tags() {
+        let filter = TagFilter {
+            include: vec![r"^\d".to_string()],
+            exclude: vec!["-alpine$".to_string()],
+            ..filter()
+        };
+        assert_eq!(
+            apply(&filter, &["1.0", "1.0-alpine", "latest", "2.0"]),
+            ["1.0", "2.0"]
+        );
+    }
+
+    #[test]
+    fn strips_prefixes() {
+        let filter = TagFilter {
+            strip_prefix: Some("v".to_string()),
+            ..filter()
+        };
+        assert_eq!(apply(&filter, &["v1.2.3", "1.2.4"]), ["1.2.3", "1.2.4"]);
+    }
+
+    #[test]
+    fn strips_prefixes_before_capturing() {
+        let filter = TagFilter {
+            strip_prefix: Some("release-".to_string()),
+            capture: Some(r"^(\d+\.\d+)\.\d+$".to_string()),
+            ..filter()
+        };
+        assert_eq!(
+            apply(&filter, &["release-1.2.3", "release-1.3", "2.0.1"]),
+            ["1.2", "2.0"]
+        );
+    }
+
+    #[test]
+    fn prefers_the_version_group() {
+        let filter = TagFilter {
+            capture: Some(r"^(jdk)-(?P<version>[\d.]+)$".to_string()),
+            ..filter()
+        };
+        assert_eq!(apply(&filter, &["jdk-21.0.1", "jre-21.0.1"]), ["21.0.1"]);
+    }
+
+    #[test]
+    fn excludes_prereleases() {
+        let filter = TagFilter {
+            prerelease: false,
+            ..filter()
+        };
+        assert_eq!(
+            apply(
+                &filter,
+                &[
+                    "1.2.3",
+                    "1.2.3-rc1",
+                    "2.0.0-beta.2",
+                    "nightly-2024-01-01",
+                    "4.0.0-M1",
+                    "1.0.0-SNAPSHOT",
+                    "release-2024",
+                    "3.0.0-ea",
+                ]
+            ),
+            ["1.2.3", "release-2024"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_regexes() {
+        let filter = TagFilter {
+            include: vec!["(".to_string()],
+            ..filter()
+        };
+        assert!(filter.check().is_err());
+        assert!(filter.apply(&["1.0".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Typed lock file entries for unit tests, so a test only changes the field it is about
+
+use std::collections::BTreeMap;
+
+use crate::lock::{
+    BaseConfig, DockerInstallation, ImageIdentifier, Installation, InstallationConfig, Layer,
+    SingleBuild, SingleVersioned, TuxWranglerConfigLocked,
+};
+
+/// A base locked by digest, resolved from `target_version`
+pub(crate) fn base(name: &str, version: &str, target_version: &str) -> BaseConfig {
+    BaseConfig {
+        name: name.to_string(),
+        version: version.to_string(),
+        target_version: Some(target_version.to_string()),
+        registry: String::new(),
+        identifier: ImageIdentifier::Digest {
+            digest: format!("sha256:{name}-{version}"),
+        },
+        package_manager: "apt".to_string(),
+        tag: None,
+        platforms: BTreeMap::new(),
+    }
+}
+
+/// A feature resolved from `target_version`, installed by a single docker step
+pub(crate) fn feature(name: &str, version: &str, target_version: &str) -> InstallationConfig {
+    InstallationConfig {
+        name: name.to_string(),
+        version: version.to_string(),
+        target_version: Some(target_version.to_string()),
+        steps: vec![Layer {
+            layer_type: Default::default(),
+            installation: Installation::Docker(DockerInstallation {
+                commands: vec![format!("RUN install {name}-{version}")],
+                dependencies: Vec::new(),
+            }),
+            copy: Default::default(),
+            build_args: Vec::new(),
+            secrets: Vec::new(),
+        }],
+        tag: None,
+    }
+}
+
+/// A build of the base with the features, targeted by their names and target versions
+pub(crate) fn build(
+    base: &BaseConfig,
+    features: &[&InstallationConfig],
+    image_name: &str,
+    image_tag: &str,
+) -> SingleBuild {
+    let target = [(&base.name, &base.target_version)]
+        .into_iter()
+        .chain(features.iter().map(|f| (&f.name, &f.target_version)))
+        .map(|(name, version)| format!("{name}-{}", version.as_deref().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join("-");
+    SingleBuild {
+        base: SingleVersioned {
+            name: base.name.clone(),
+            version: base.version.clone(),
+        },
+        features: features
+            .iter()
+            .map(|f| SingleVersioned {
+                name: f.name.clone(),
+                version: f.version.clone(),
+            })
+            .collect(),
+        target,
+        image_name: image_name.to_string(),
+        image_tag: image_tag.to_string(),
+        alias_tags: Vec::new(),
+        platforms: Vec::new(),
+        labels: BTreeMap::new(),
+    }
+}
+
+pub(crate) fn lock(
+    bases: Vec<BaseConfig>,
+    features: Vec<InstallationConfig>,
+    builds: Vec<SingleBuild>,
+) -> TuxWranglerConfigLocked {
+    TuxWranglerConfigLocked {
+        registry: "example.com".to_string(),
+        config_hash: None,
+        tool_version: None,
+        bases,
+        features,
+        builds,
+        build_args: BTreeMap::new(),
+        secrets: BTreeMap::new(),
+    }
+}
@@ -1,21 +1,37 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 
+use anyhow::anyhow;
 use anyhow::Context;
 use futures::future::join_all;
 use futures::TryFutureExt;
 use itertools::iproduct;
+use itertools::Either;
 use itertools::Itertools;
+use log::debug;
 use log::warn;
+use serde_json::Value;
 
 use crate::config::BaseDefinition;
+use crate::config::Build;
 use crate::config::BuildDefinition;
+use crate::config::CommandFetchVersion;
 use crate::config::DockerFetchVersion;
 use crate::config::FeatureDefinition;
 use crate::config::FetchVersion;
+use crate::config::GitFetchVersion;
 use crate::config::GithubFetchVersion;
+use crate::config::HttpFetchVersion;
+use crate::config::RegistryFetchVersion;
+use crate::config::TagFilter;
+use crate::config::VersionFrom;
 use crate::config::VersionedDefinition;
+use crate::constraint::is_constraint;
+use crate::constraint::Constraint;
 use crate::docker::Docker;
+use crate::git::Git;
 use crate::github::Github;
+use crate::http::Http;
 use crate::lock::BaseConfig;
 use crate::lock::DockerInstallation;
 use crate::lock::ImageIdentifier;
@@ -26,12 +42,18 @@ use crate::lock::RpmInstallation;
 use crate::lock::RpmInstallationMethod;
 use crate::lock::SingleBuild;
 use crate::lock::SingleVersioned;
+use crate::registry::Registry;
+use crate::version::find_tag;
+use crate::version::handlebars_data;
 use crate::version::populate_name_template;
 use crate::version::populate_template;
+use crate::version::render_templates;
+use crate::version::version_match;
 use crate::Clients;
 use crate::Result;
 use crate::TuxWranglerConfig;
 use crate::TuxWranglerConfigLocked;
+use crate::TOOL_VERSION;
 
 type Name = String;
 type TargetVersion = String;
@@ -41,17 +63,112 @@ type NamedActualVersions = HashMap<Name, ActualVersions>;
 type BaseConfigs = HashMap<SingleVersioned, BaseConfig>;
 type FeatureConfigs = HashMap<SingleVersioned, InstallationConfig>;
 
+/// Entries of an existing lock file that are kept as they are instead of being resolved again
+pub(crate) struct Pins<'a> {
+    lock: &'a TuxWranglerConfigLocked,
+    /// The entries that should be resolved again, as a name and an optional target version
+    only: Vec<(Name, Option<TargetVersion>)>,
+}
+
+impl<'a> Pins<'a> {
+    /// Pin every entry of the lock file except the ones selected by `only` (`name` or `name:version`)
+    pub(crate) fn new(lock: &'a TuxWranglerConfigLocked, only: &[String]) -> Self {
+        Self {
+            lock,
+            only: only
+                .iter()
+                .map(|selection| match selection.split_once(':') {
+                    Some((name, version)) => (name.to_string(), Some(version.to_string())),
+                    None => (selection.clone(), None),
+                })
+                .collect(),
+        }
+    }
+
+    fn is_selected(&self, name: &str, target_version: &str) -> bool {
+        self.only
+            .iter()
+            .any(|(n, v)| n == name && v.as_ref().is_none_or(|v| v == target_version))
+    }
+
+    /// The locked base for a target version, if it should be kept
+    fn base(&self, name: &str, target_version: &str) -> Option<&'a BaseConfig> {
+        if self.is_selected(name, target_version) {
+            return None;
+        }
+        self.lock.resolved_base(name, target_version)
+    }
+
+    /// The locked feature for a target version, if it should be kept
+    fn feature(&self, name: &str, target_version: &str) -> Option<&'a InstallationConfig> {
+        if self.is_selected(name, target_version) {
+            return None;
+        }
+        self.lock.resolved_feature(name, target_version)
+    }
+
+    /// The locked actual version for a target version, if it should be kept
+    fn version(&self, name: &str, target_version: &str) -> Option<ActualVersion> {
+        if self.is_selected(name, target_version) {
+            return None;
+        }
+        self.lock
+            .resolved_version(name, target_version)
+            .map(String::from)
+    }
+
+    /// Make sure every selected entry exists in the config
+    fn check(&self, config: &TuxWranglerConfig) -> Result<()> {
+        for (name, version) in &self.only {
+            let defined = config
+                .bases
+                .iter()
+                .map(|base| &base.definition)
+                .chain(config.features.iter().map(|feature| &feature.definition))
+                .any(|definition| {
+                    &definition.name() == name
+                        && version
+                            .as_ref()
+                            .is_none_or(|v| definition.versioned.versions.contains(v))
+                });
+            if !defined {
+                return Err(anyhow!(
+                    "'{name}{}' does not match any base or feature in the config",
+                    version
+                        .as_ref()
+                        .map(|v| format!(":{v}"))
+                        .unwrap_or_default()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 impl TuxWranglerConfig {
     pub(crate) async fn build_locked(
         self,
         clients: &mut Clients,
+        pins: Option<&Pins<'_>>,
+        fallback: Option<&TuxWranglerConfigLocked>,
     ) -> Result<TuxWranglerConfigLocked> {
-        let actual_versions = self.actual_versions(clients).await?;
-        let base_configs = self.base_configs(clients, &actual_versions).await?;
-        let feature_configs = self.feature_configs(&actual_versions)?;
-        let individual_builds = self.individual_builds(&base_configs, &feature_configs)?;
+        let config_hash = self.hash()?;
+        if let Some(pins) = pins {
+            pins.check(&self)?;
+        }
+        let actual_versions = self.actual_versions(clients, pins, fallback).await?;
+        let base_configs = self
+            .base_configs(clients, &actual_versions, pins, fallback)
+            .await?;
+        let feature_configs = self
+            .feature_configs(clients, &actual_versions, pins, fallback)
+            .await?;
+        let individual_builds =
+            self.individual_builds(&actual_versions, &base_configs, &feature_configs)?;
         Ok(TuxWranglerConfigLocked {
             registry: self.registry,
+            config_hash: Some(config_hash),
+            tool_version: Some(TOOL_VERSION.to_string()),
             bases: base_configs
                 .values()
                 .sorted_by(|a, b| {
@@ -67,23 +184,36 @@ impl TuxWranglerConfig {
                 .cloned()
                 .collect(),
             builds: individual_builds,
+            build_args: self.build_args,
+            secrets: self.secrets,
         })
     }
 
-    async fn actual_versions(&self, clients: &mut Clients) -> Result<NamedActualVersions> {
+    async fn actual_versions(
+        &self,
+        clients: &mut Clients,
+        pins: Option<&Pins<'_>>,
+        fallback: Option<&TuxWranglerConfigLocked>,
+    ) -> Result<NamedActualVersions> {
         let mut versions = NamedActualVersions::new();
         for base in &self.bases {
             if let Some(existing) = versions.get_mut(&base.name()) {
-                existing.extend(base.actual_versions(clients).await?);
+                existing.extend(base.actual_versions(clients, pins, fallback).await?);
             } else {
-                versions.insert(base.name(), base.actual_versions(clients).await?);
+                versions.insert(
+                    base.name(),
+                    base.actual_versions(clients, pins, fallback).await?,
+                );
             }
         }
         for feature in &self.features {
             if let Some(existing) = versions.get_mut(&feature.name()) {
-                existing.extend(feature.actual_versions(clients).await?);
+                existing.extend(feature.actual_versions(clients, pins, fallback).await?);
             } else {
-                versions.insert(feature.name(), feature.actual_versions(clients).await?);
+                versions.insert(
+                    feature.name(),
+                    feature.actual_versions(clients, pins, fallback).await?,
+                );
             }
         }
         Ok(versions)
@@ -105,12 +235,43 @@ impl TuxWranglerConfig {
             .collect()
     }
 
-    /// Compute all builds
-    fn individual_builds(
+    /// The declared target versions selected by a build definition.
+    /// A constraint selects every declared version whose actual version satisfies it,
+    /// falling back to the target version when the actual version is unknown.
+    fn selected_versions(
+        bd: &BuildDefinition,
+        declared: Vec<String>,
+        actual_version: &dyn Fn(&str, &str) -> Option<ActualVersion>,
+    ) -> Vec<String> {
+        match bd {
+            BuildDefinition::Named(_) => declared,
+            BuildDefinition::Versioned(v) => v
+                .versions
+                .iter()
+                .flat_map(|version| match Constraint::parse(version) {
+                    Ok(constraint) if is_constraint(version) => declared
+                        .iter()
+                        .filter(|target| {
+                            constraint.matches(
+                                &actual_version(&v.name, target)
+                                    .unwrap_or_else(|| target.to_string()),
+                            )
+                        })
+                        .cloned()
+                        .collect(),
+                    _ => vec![version.clone()],
+                })
+                .unique()
+                .collect(),
+        }
+    }
+
+    /// Expand every build definition into each combination of target base and feature versions.
+    /// `actual_version` looks up the actual version of a name and target version, when known.
+    pub(crate) fn build_combinations(
         &self,
-        base_configs: &BaseConfigs,
-        feature_configs: &FeatureConfigs,
-    ) -> Result<Vec<SingleBuild>> {
+        actual_version: &dyn Fn(&str, &str) -> Option<ActualVersion>,
+    ) -> Vec<(&Build, SingleVersioned, Vec<SingleVersioned>)> {
         self.builds
             .iter()
             .flat_map(|build| {
@@ -119,51 +280,59 @@ impl TuxWranglerConfig {
                     .features
                     .iter()
                     .map(|feature_set| {
-                        feature_set
-                            .iter()
-                            .flat_map(|bd| {
-                                let versions = match bd {
-                                    BuildDefinition::Named(target_feature) => {
-                                        self.feature_versions(target_feature)
-                                    }
-                                    BuildDefinition::Versioned(v) => v.versions.clone(),
-                                };
-                                let name = bd.name();
-                                versions
-                                    .into_iter()
-                                    .map(|version| SingleVersioned {
-                                        name: name.clone(),
-                                        version,
-                                    })
-                                    .collect::<Vec<_>>()
-                            })
+                        feature_set.iter().flat_map(|bd| {
+                            let name = bd.name();
+                            let versions = Self::selected_versions(
+                                bd,
+                                self.feature_versions(&name),
+                                actual_version,
+                            );
+                            versions
+                                .into_iter()
+                                .map(|version| SingleVersioned {
+                                    name: name.clone(),
+                                    version,
+                                })
+                                .collect::<Vec<_>>()
+                        })
                     })
                     // apply a cartesian product to achieve all combinations from each feature set
                     .multi_cartesian_product();
 
                 // create base-version pairs
-                let bases = build
-                    .bases
-                    .iter()
-                    .flat_map(|bd| {
-                        let versions = match bd {
-                            BuildDefinition::Named(target_feature) => {
-                                self.base_versions(target_feature)
-                            }
-                            BuildDefinition::Versioned(v) => v.versions.clone(),
-                        };
-                        let name = bd.name();
-                        versions
-                            .into_iter()
-                            .map(|version| SingleVersioned {
-                                name: name.clone(),
-                                version,
-                            })
-                            .collect::<Vec<_>>()
-                    });
+                let bases = build.bases.iter().flat_map(|bd| {
+                    let name = bd.name();
+                    let versions =
+                        Self::selected_versions(bd, self.base_versions(&name), actual_version);
+                    versions
+                        .into_iter()
+                        .map(|version| SingleVersioned {
+                            name: name.clone(),
+                            version,
+                        })
+                        .collect::<Vec<_>>()
+                });
 
                 // Perform a cartesian product between the bases and feature groups
-                iproduct!(bases, feature_groups).map(|(base, features)| {
+                iproduct!(bases, feature_groups)
+                    .map(move |(base, features)| (build, base, features))
+            })
+            .collect()
+    }
+
+    /// Compute all builds
+    fn individual_builds(
+        &self,
+        actual_versions: &NamedActualVersions,
+        base_configs: &BaseConfigs,
+        feature_configs: &FeatureConfigs,
+    ) -> Result<Vec<SingleBuild>> {
+        self.build_combinations(&|name, target| {
+            actual_versions.get(name)?.get(target).cloned()
+        })
+            .into_iter()
+            .map(|(build, base, features)| {
+                    let platforms = self.build_platforms(build, &base)?;
                     base_configs
                         .get(&base).map(|p| (p.single_versioned(), p.tag.as_ref()))
                         .context(format!(
@@ -176,16 +345,45 @@ impl TuxWranglerConfig {
                                     feature.name, feature.version
                                 ))).collect::<Result<Vec<(SingleVersioned, Option<&String>)>>>().map(|features| (p.0, p.1, features.into_iter().unzip::<SingleVersioned, Option<&String>, Vec<SingleVersioned>, Vec<Option<&String>>>()))
                         })
-                        .and_then(|(base, base_tag, (features, feature_tags))| single_build(&build.image_name, &build.image_tag, base, base_tag, features, feature_tags))
-                })
+                        .and_then(|(base, base_tag, (features, feature_tags))| single_build(build, base, base_tag, features, feature_tags, platforms))
             })
             .collect::<Result<_>>()
     }
 
+    /// The platforms of a build on a target base, defaulting to the platforms of the base
+    fn build_platforms(&self, build: &Build, base: &SingleVersioned) -> Result<Vec<String>> {
+        let base_platforms = self
+            .bases
+            .iter()
+            .filter(|b| {
+                b.name() == base.name && b.definition.versioned.versions.contains(&base.version)
+            })
+            .flat_map(|b| b.platforms.iter().cloned())
+            .unique()
+            .collect::<Vec<_>>();
+        if build.platforms.is_empty() {
+            return Ok(base_platforms);
+        }
+        if let Some(platform) = build
+            .platforms
+            .iter()
+            .find(|p| !base_platforms.is_empty() && !base_platforms.contains(p))
+        {
+            return Err(anyhow!(
+                "Platform '{platform}' of build '{}' is not one of the platforms of base '{base}': {}",
+                build.image_name,
+                base_platforms.join(", ")
+            ));
+        }
+        Ok(build.platforms.clone())
+    }
+
     async fn base_configs(
         &self,
         clients: &mut Clients,
         actual_versions: &NamedActualVersions,
+        pins: Option<&Pins<'_>>,
+        fallback: Option<&TuxWranglerConfigLocked>,
     ) -> Result<BaseConfigs> {
         let mut bases = BaseConfigs::new();
         for base in &self.bases {
@@ -211,24 +409,76 @@ impl TuxWranglerConfig {
                     .map(|tag| actual_version.populate_template(tag))
                     .transpose()?;
                 let image = actual_version.populate_template(&base.image)?;
-                let image_identifier = match clients.docker.digest(&image).await {
-                    Ok(digest) => ImageIdentifier::Digest { digest },
-                    Err(e) => {
-                        if let Some(tag) = Docker::tag(&image) {
-                            warn!("No digest was found for '{image}', using tag '{tag}' instead.");
-                            ImageIdentifier::Tag { tag }
-                        } else {
-                            return Err(e);
+                let pinned = pins
+                    .and_then(|pins| pins.base(&name, version))
+                    .filter(|pinned| pinned.version == actual_version.version);
+                let image_identifier = match pinned {
+                    Some(pinned) => {
+                        debug!("Keeping locked identifier for '{image}'");
+                        pinned.identifier.clone()
+                    }
+                    None => match clients.digest(&image).await {
+                        Ok(digest) => ImageIdentifier::Digest { digest },
+                        Err(e) => {
+                            let locked = fallback
+                                .and_then(|lock| lock.resolved_base(&name, version))
+                                .filter(|locked| locked.version == actual_version.version);
+                            if let Some(locked) = locked {
+                                debug!("Using locked identifier for '{image}'");
+                                locked.identifier.clone()
+                            } else if clients.cache.offline() {
+                                return Err(e.context(format!(
+                                    "'{name}:{version}' has never been resolved, run `update` without `--offline` first"
+                                )));
+                            } else if let Some(tag) = Docker::tag(&image) {
+                                warn!(
+                                    "No digest was found for '{image}', using tag '{tag}' instead."
+                                );
+                                ImageIdentifier::Tag { tag }
+                            } else {
+                                return Err(e);
+                            }
+                        }
+                    },
+                };
+                let platforms = match &image_identifier {
+                    ImageIdentifier::Digest { digest } if !base.platforms.is_empty() => {
+                        // Digests are immutable, so the platforms of a locked digest can be kept
+                        let locked = pinned
+                            .into_iter()
+                            .chain(fallback.and_then(|lock| lock.resolved_base(&name, version)))
+                            .find(|locked| {
+                                locked.identifier == image_identifier
+                                    && base
+                                        .platforms
+                                        .iter()
+                                        .all(|p| locked.platforms.contains_key(p))
+                            });
+                        match locked {
+                            Some(locked) => base
+                                .platforms
+                                .iter()
+                                .map(|p| (p.clone(), locked.platforms[p].clone()))
+                                .collect(),
+                            None => {
+                                clients
+                                    .registry
+                                    .platform_digests(&image, digest, &base.platforms)
+                                    .await?
+                            }
                         }
                     }
+                    _ => BTreeMap::new(),
                 };
                 let base_config = BaseConfig {
                     name: name.clone(),
                     registry: Docker::registry(&image),
                     version: actual_version.version,
+                    target_version: Some(version.clone()),
                     package_manager: base.package_manager.clone(),
                     tag: tag.clone(),
                     identifier: image_identifier,
+                    platforms,
                 };
                 bases.insert(single_versioned, base_config);
             }
@@ -236,7 +486,13 @@ impl TuxWranglerConfig {
         Ok(bases)
     }
 
-    fn feature_configs(&self, actual_versions: &NamedActualVersions) -> Result<FeatureConfigs> {
+    async fn feature_configs(
+        &self,
+        clients: &mut Clients,
+        actual_versions: &NamedActualVersions,
+        pins: Option<&Pins<'_>>,
+        fallback: Option<&TuxWranglerConfigLocked>,
+    ) -> Result<FeatureConfigs> {
         let mut features = FeatureConfigs::new();
         for feature in &self.features {
             let name = feature.name();
@@ -254,20 +510,64 @@ impl TuxWranglerConfig {
                         .context(format!("Version '{version}' not found for '{name}'"))?
                         .clone(),
                 };
+                let pinned = pins
+                    .and_then(|pins| pins.feature(&name, version))
+                    .filter(|pinned| pinned.version == actual_version.version);
+                if let Some(pinned) = pinned {
+                    debug!("Keeping locked steps for '{name}:{version}'");
+                    features.insert(single_versioned, pinned.clone());
+                    continue;
+                }
                 let tag = feature
                     .definition
                     .version_tag
                     .as_ref()
                     .map(|tag| actual_version.populate_template(tag))
                     .transpose()?;
-                let feature_config = InstallationConfig {
-                    name: name.clone(),
-                    steps: feature
+                let mut data = handlebars_data(&actual_version.version);
+                let mut locked_steps = None;
+                // Steps of GitHub releases can use the release and its assets
+                if let Some(FetchVersion::Github(fetch_version)) = &feature.definition.fetch_version
+                {
+                    if matches!(fetch_version.version_from, VersionFrom::Release) {
+                        if clients.cache.offline() {
+                            // The release isn't needed when the lock file has the steps it produced
+                            let locked = fallback
+                                .and_then(|lock| lock.resolved_feature(&name, version))
+                                .filter(|locked| locked.version == actual_version.version)
+                                .context(format!(
+                                    "The release of '{name}:{}' has never been resolved, run `update` without `--offline` first",
+                                    actual_version.version
+                                ))?;
+                            debug!("Using locked steps for '{name}:{version}'");
+                            locked_steps = Some(locked.steps.clone());
+                        } else {
+                            let project =
+                                single_versioned.populate_template(&fetch_version.project)?;
+                            data["release"] = clients
+                                .github(fetch_version.host.as_deref())?
+                                .release(&project, fetch_version, &actual_version.version)
+                                .await?;
+                        }
+                    }
+                }
+                let steps = match locked_steps {
+                    Some(steps) => steps,
+                    None => feature
                         .steps
                         .iter()
-                        .map(|step| step.populate(&actual_version))
-                        .collect::<Result<_>>()?,
+                        .map(|step| step.populate(&data))
+                        .collect::<Result<_>>()
+                        .context(format!(
+                            "Unable to populate the steps of '{name}' for version '{}'",
+                            actual_version.version
+                        ))?,
+                };
+                let feature_config = InstallationConfig {
+                    name: name.clone(),
+                    steps,
                     version: actual_version.version,
+                    target_version: Some(version.clone()),
                     tag: tag.clone(),
                 };
                 features.insert(single_versioned, feature_config);
@@ -278,8 +578,15 @@ impl TuxWranglerConfig {
 }
 
 impl BaseDefinition {
-    async fn actual_versions(&self, clients: &mut Clients) -> Result<ActualVersions> {
-        self.definition.actual_versions(clients).await
+    async fn actual_versions(
+        &self,
+        clients: &mut Clients,
+        pins: Option<&Pins<'_>>,
+        fallback: Option<&TuxWranglerConfigLocked>,
+    ) -> Result<ActualVersions> {
+        self.definition
+            .actual_versions(clients, pins, fallback)
+            .await
     }
 
     fn name(&self) -> Name {
@@ -288,8 +595,15 @@ impl BaseDefinition {
 }
 
 impl FeatureDefinition {
-    async fn actual_versions(&self, clients: &mut Clients) -> Result<ActualVersions> {
-        self.definition.actual_versions(clients).await
+    async fn actual_versions(
+        &self,
+        clients: &mut Clients,
+        pins: Option<&Pins<'_>>,
+        fallback: Option<&TuxWranglerConfigLocked>,
+    ) -> Result<ActualVersions> {
+        self.definition
+            .actual_versions(clients, pins, fallback)
+            .await
     }
 
     fn name(&self) -> Name {
@@ -298,18 +612,76 @@ impl FeatureDefinition {
 }
 
 impl VersionedDefinition {
-    async fn actual_versions(&self, clients: &mut Clients) -> Result<ActualVersions> {
-        Ok(if let Some(fetch_version) = &self.fetch_version {
-            fetch_version
-                .fetch_versions(&self.versioned.versions, clients)
-                .await?
+    async fn actual_versions(
+        &self,
+        clients: &mut Clients,
+        pins: Option<&Pins<'_>>,
+        fallback: Option<&TuxWranglerConfigLocked>,
+    ) -> Result<ActualVersions> {
+        let name = self.name();
+        // Keep the locked version for every pinned entry and only resolve the rest
+        let (mut actual_versions, unpinned): (ActualVersions, Vec<TargetVersion>) =
+            self.versioned.versions.iter().partition_map(|version| {
+                match pins.and_then(|pins| pins.version(&name, version)) {
+                    Some(actual) => Either::Left((version.clone(), actual)),
+                    None => Either::Right(version.clone()),
+                }
+            });
+        if unpinned.is_empty() {
+            return Ok(actual_versions);
+        }
+        actual_versions.extend(if let Some(fetch_version) = &self.fetch_version {
+            if clients.cache.offline() {
+                self.offline_versions(fetch_version, &unpinned, clients, fallback)
+                    .await?
+            } else {
+                fetch_version.fetch_versions(&unpinned, clients).await?
+            }
         } else {
-            self.versioned
-                .versions
+            if let Some(constraint) = unpinned.iter().find(|version| is_constraint(version)) {
+                return Err(anyhow!(
+                    "Version constraint '{constraint}' for '{name}' requires a `fetch-version`"
+                ));
+            }
+            unpinned
                 .iter()
                 .map(|version| (version.clone(), version.clone()))
                 .collect()
-        })
+        });
+        Ok(actual_versions)
+    }
+
+    /// Resolve each version from the cache on its own, using the previous lock file
+    /// for the versions that can't be resolved offline
+    async fn offline_versions(
+        &self,
+        fetch_version: &FetchVersion,
+        versions: &[TargetVersion],
+        clients: &mut Clients,
+        fallback: Option<&TuxWranglerConfigLocked>,
+    ) -> Result<ActualVersions> {
+        let name = self.name();
+        let mut actual_versions = ActualVersions::new();
+        for version in versions {
+            match fetch_version
+                .fetch_versions(std::slice::from_ref(version), clients)
+                .await
+            {
+                Ok(fetched) => actual_versions.extend(fetched),
+                Err(e) => {
+                    let locked = fallback
+                        .and_then(|lock| lock.resolved_version(&name, version))
+                        .ok_or_else(|| {
+                            e.context(format!(
+                                "'{name}:{version}' has never been resolved, run `update` without `--offline` first"
+                            ))
+                        })?;
+                    debug!("Using locked version '{locked}' for '{name}:{version}'");
+                    actual_versions.insert(version.clone(), locked.to_string());
+                }
+            }
+        }
+        Ok(actual_versions)
     }
 
     fn name(&self) -> Name {
@@ -319,7 +691,7 @@ impl VersionedDefinition {
 
 impl SingleVersioned {
     fn populate_template(&self, template: &str) -> Result<String> {
-        populate_template(template, &[self.version.clone()])
+        populate_template(template, std::slice::from_ref(&self.version))
             .map(|map| map.values().map(|s| s.to_string()).collect())
     }
 
@@ -338,11 +710,31 @@ impl FetchVersion {
         clients: &mut Clients,
     ) -> Result<ActualVersions> {
         match self {
+            // Versions reported by images and commands are never cached
+            FetchVersion::Docker(_) | FetchVersion::Command(_) if clients.cache.offline() => Err(
+                anyhow!("Versions from Docker images and commands can't be fetched offline"),
+            ),
             FetchVersion::Docker(fetch_version) => {
                 clients.docker.fetch_versions(fetch_version, versions).await
             }
             FetchVersion::Github(fetch_version) => {
-                clients.gh.fetch_versions(fetch_version, versions).await
+                clients
+                    .github(fetch_version.host.as_deref())?
+                    .fetch_versions(fetch_version, versions)
+                    .await
+            }
+            FetchVersion::Registry(fetch_version) => {
+                clients
+                    .registry
+                    .fetch_versions(fetch_version, versions)
+                    .await
+            }
+            FetchVersion::Http(fetch_version) => {
+                clients.http.fetch_versions(fetch_version, versions).await
+            }
+            FetchVersion::Command(fetch_version) => fetch_version.fetch_versions(versions).await,
+            FetchVersion::Git(fetch_version) => {
+                clients.git.fetch_versions(fetch_version, versions).await
             }
         }
     }
@@ -358,8 +750,12 @@ impl Docker {
             populate_template(&fetch_version.image, versions)?
                 .iter()
                 .map(|(target_version, image)| {
-                    self.version(image, &fetch_version.command)
-                        .map_ok(|version| (target_version.clone(), version))
+                    self.version(image, &fetch_version.command).and_then(
+                        move |version| async move {
+                            reported_version(&fetch_version.filter, target_version, version, image)
+                                .map(|version| (target_version.clone(), version))
+                        },
+                    )
                 }),
         )
         .await
@@ -368,6 +764,45 @@ impl Docker {
     }
 }
 
+impl CommandFetchVersion {
+    async fn fetch_versions(&self, versions: &[String]) -> Result<ActualVersions> {
+        join_all(versions.iter().map(|target_version| async move {
+            let args = SingleVersioned {
+                name: self.command.clone(),
+                version: target_version.clone(),
+            }
+            .populate_templates(&self.args)?;
+            let version = self.version(&args).await?;
+            reported_version(&self.filter, target_version, version, &self.command)
+                .map(|version| (target_version.clone(), version))
+        }))
+        .await
+        .into_iter()
+        .collect::<Result<ActualVersions>>()
+    }
+}
+
+/// Filter a version reported by a single source, which also has to satisfy a constraint
+fn reported_version(
+    filter: &TagFilter,
+    target_version: &str,
+    version: String,
+    source: &str,
+) -> Result<ActualVersion> {
+    let version = filter
+        .apply(std::slice::from_ref(&version))?
+        .pop()
+        .context(format!(
+            "Version '{version}' from '{source}' was removed by the tag filter"
+        ))?;
+    if is_constraint(target_version) && !version_match(target_version, &version) {
+        return Err(anyhow!(
+            "Version '{version}' from '{source}' does not satisfy '{target_version}'"
+        ));
+    }
+    Ok(version)
+}
+
 impl Github {
     async fn fetch_versions(
         &mut self,
@@ -378,13 +813,74 @@ impl Github {
         for (target_version, project) in populate_template(&fetch_version.project, versions)? {
             actual_versions.insert(
                 target_version.clone(),
-                self.version(
-                    &target_version,
-                    &fetch_version.org,
-                    &project,
-                    &fetch_version.version_from,
-                )
-                .await?,
+                self.version(&target_version, &project, fetch_version)
+                    .await?,
+            );
+        }
+        Ok(actual_versions)
+    }
+}
+
+impl Registry {
+    async fn fetch_versions(
+        &mut self,
+        fetch_version: &RegistryFetchVersion,
+        versions: &[String],
+    ) -> Result<ActualVersions> {
+        let mut actual_versions = ActualVersions::new();
+        for (target_version, image) in populate_template(&fetch_version.image, versions)? {
+            let tags = fetch_version.filter.apply(&self.tags(&image).await?)?;
+            actual_versions.insert(
+                target_version.clone(),
+                find_tag(&target_version, &tags, fetch_version.order).context(format!(
+                    "Unable to find tag for '{target_version}' in '{image}'"
+                ))?,
+            );
+        }
+        Ok(actual_versions)
+    }
+}
+
+impl Http {
+    async fn fetch_versions(
+        &mut self,
+        fetch_version: &HttpFetchVersion,
+        versions: &[String],
+    ) -> Result<ActualVersions> {
+        let mut actual_versions = ActualVersions::new();
+        for (target_version, url) in populate_template(&fetch_version.url, versions)? {
+            let candidates = fetch_version.filter.apply(
+                &self
+                    .versions(&url, &fetch_version.json_path, &fetch_version.headers)
+                    .await?,
+            )?;
+            actual_versions.insert(
+                target_version.clone(),
+                find_tag(&target_version, &candidates, fetch_version.order).context(format!(
+                    "Unable to find version for '{target_version}' in '{url}'"
+                ))?,
+            );
+        }
+        Ok(actual_versions)
+    }
+}
+
+impl Git {
+    async fn fetch_versions(
+        &mut self,
+        fetch_version: &GitFetchVersion,
+        versions: &[String],
+    ) -> Result<ActualVersions> {
+        let mut actual_versions = ActualVersions::new();
+        for (target_version, url) in populate_template(&fetch_version.url, versions)? {
+            let refs = fetch_version
+                .filter
+                .apply(&self.refs(&url, &fetch_version.version_from).await?)?;
+            actual_versions.insert(
+                target_version.clone(),
+                find_tag(&target_version, &refs, fetch_version.order).context(format!(
+                    "Unable to find ref for '{target_version}' in '{url}'"
+                ))?,
             );
         }
         Ok(actual_versions)
@@ -392,7 +888,7 @@ impl Github {
 }
 
 impl BaseConfig {
-    fn single_versioned(&self) -> SingleVersioned {
+    pub(crate) fn single_versioned(&self) -> SingleVersioned {
         SingleVersioned {
             name: self.name.clone(),
             version: self.version.clone(),
@@ -401,7 +897,7 @@ impl BaseConfig {
 }
 
 impl InstallationConfig {
-    fn single_versioned(&self) -> SingleVersioned {
+    pub(crate) fn single_versioned(&self) -> SingleVersioned {
         SingleVersioned {
             name: self.name.clone(),
             version: self.version.clone(),
@@ -410,42 +906,43 @@ impl InstallationConfig {
 }
 
 impl Layer {
-    fn populate(&self, single_versioned: &SingleVersioned) -> Result<Self> {
+    fn populate(&self, data: &Value) -> Result<Self> {
         Ok(Self {
-            installation: self.installation.populate(single_versioned)?,
+            installation: self.installation.populate(data)?,
             layer_type: self.layer_type.clone(),
             copy: self.copy.clone(),
+            build_args: self.build_args.clone(),
+            secrets: self.secrets.clone(),
         })
     }
 }
 
 impl Installation {
-    fn populate(&self, single_versioned: &SingleVersioned) -> Result<Self> {
+    fn populate(&self, data: &Value) -> Result<Self> {
         Ok(match self {
-            Installation::Docker(d) => Installation::Docker(d.populate(single_versioned)?),
-            Installation::Rpm(r) => Installation::Rpm(r.populate(single_versioned)?),
+            Installation::Docker(d) => Installation::Docker(d.populate(data)?),
+            Installation::Rpm(r) => Installation::Rpm(r.populate(data)?),
         })
     }
 }
 
 impl DockerInstallation {
-    fn populate(&self, single_versioned: &SingleVersioned) -> Result<Self> {
+    fn populate(&self, data: &Value) -> Result<Self> {
         Ok(Self {
-            commands: single_versioned.populate_templates(&self.commands)?,
-            dependencies: single_versioned.populate_templates(&self.dependencies)?,
+            commands: render_templates(&self.commands, data)?,
+            dependencies: render_templates(&self.dependencies, data)?,
         })
     }
 }
 
 impl RpmInstallation {
-    fn populate(&self, single_versioned: &SingleVersioned) -> Result<Self> {
+    fn populate(&self, data: &Value) -> Result<Self> {
         Ok(Self {
             installation_methods: self
                 .installation_methods
                 .iter()
                 .map(|(key, installation_method)| {
-                    single_versioned
-                        .populate_templates(&installation_method.script)
+                    render_templates(&installation_method.script, data)
                         .map(|script| (key.clone(), RpmInstallationMethod { script }))
                 })
                 .collect::<Result<_>>()?,
@@ -454,16 +951,29 @@ impl RpmInstallation {
 }
 
 fn single_build(
-    image_name_template: &str,
-    image_tag_template: &str,
+    build: &Build,
     base: SingleVersioned,
     base_tag: Option<&String>,
     features: Vec<SingleVersioned>,
     feature_tags: Vec<Option<&String>>,
+    platforms: Vec<String>,
 ) -> Result<SingleBuild> {
     Ok(SingleBuild {
-        image_name: populate_name_template(image_name_template, &base, &features)?,
-        image_tag: populate_name_template(image_tag_template, &base, &features)?,
+        image_name: populate_name_template(&build.image_name, &base, &features)?,
+        image_tag: populate_name_template(&build.image_tag, &base, &features)?,
+        alias_tags: build
+            .alias_tags
+            .iter()
+            .map(|template| populate_name_template(template, &base, &features))
+            .collect::<Result<_>>()?,
+        labels: build
+            .labels
+            .iter()
+            .map(|(name, template)| {
+                populate_name_template(template, &base, &features)
+                    .map(|label| (name.clone(), label))
+            })
+            .collect::<Result<_>>()?,
         base,
         features,
         target: base_tag
@@ -471,5 +981,6 @@ fn single_build(
             .chain(feature_tags.into_iter().flatten())
             .filter(|tag| !tag.is_empty())
             .join("-"),
+        platforms,
     })
 }
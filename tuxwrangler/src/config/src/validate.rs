@@ -0,0 +1,777 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    ops::Range,
+};
+
+use handlebars::{Handlebars, Template};
+use itertools::Itertools;
+use regex::Regex;
+use reqwest::Url;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use toml_edit::{ImDocument, Item, Table};
+
+use crate::{
+    config::{BuildDefinition, FetchVersion, VersionFrom, VersionedDefinition},
+    constraint::{is_constraint, Constraint},
+    github::sample_release,
+    lock::{Installation, SingleVersioned},
+    platform::Platform,
+    version::{handlebars_data, name_template_data},
+    TuxWranglerConfig,
+};
+
+/// The version used to render templates that are populated with a fetched version.
+/// It has enough segments for any `versions.N` lookup a template is likely to make.
+const SAMPLE_VERSION: &str = "1.2.3.4.5.6.7.8";
+
+/// A problem found in a config file, along with where it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub message: String,
+    /// 1 based line number
+    pub line: usize,
+    /// 1 based column number
+    pub column: usize,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// A step in the path to a value in the config document
+#[derive(Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl From<&str> for Segment {
+    fn from(key: &str) -> Self {
+        Segment::Key(key.to_string())
+    }
+}
+
+impl From<usize> for Segment {
+    fn from(index: usize) -> Self {
+        Segment::Index(index)
+    }
+}
+
+/// Build a path to a value in the config document
+macro_rules! path {
+    ($($segment:expr),* $(,)?) => {
+        [$(Segment::from($segment)),*]
+    };
+}
+
+/// A node of the config document that can be located in the source
+#[derive(Clone, Copy)]
+enum Node<'a> {
+    Item(&'a Item),
+    Table(&'a Table),
+    Value(&'a toml_edit::Value),
+}
+
+impl<'a> Node<'a> {
+    fn get(self, segment: &Segment) -> Option<Node<'a>> {
+        match (self, segment) {
+            (Node::Item(Item::Table(table)), _) => Node::Table(table).get(segment),
+            (Node::Item(Item::Value(value)), _) => Node::Value(value).get(segment),
+            (Node::Item(Item::ArrayOfTables(tables)), Segment::Index(i)) => {
+                tables.get(*i).map(Node::Table)
+            }
+            (Node::Table(table), Segment::Key(key)) => table.get(key).map(Node::Item),
+            (Node::Value(toml_edit::Value::InlineTable(table)), Segment::Key(key)) => {
+                table.get(key).map(Node::Value)
+            }
+            (Node::Value(toml_edit::Value::Array(array)), Segment::Index(i)) => {
+                array.get(*i).map(Node::Value)
+            }
+            _ => None,
+        }
+    }
+
+    fn span(self) -> Option<Range<usize>> {
+        match self {
+            Node::Item(item) => item.span(),
+            Node::Table(table) => table.span(),
+            Node::Value(value) => value.span(),
+        }
+    }
+}
+
+/// Collects errors for a config file, locating each one in the source
+struct Validator<'a> {
+    text: &'a str,
+    document: ImDocument<&'a str>,
+    errors: Vec<ConfigError>,
+}
+
+impl<'a> Validator<'a> {
+    /// Record an error at the deepest part of the path that exists in the document
+    fn error(&mut self, path: &[Segment], message: String) {
+        let mut node = Node::Table(self.document.as_table());
+        let mut offset = node.span().map(|span| span.start).unwrap_or_default();
+        for segment in path {
+            match node.get(segment) {
+                Some(next) => {
+                    node = next;
+                    if let Some(span) = node.span() {
+                        offset = span.start;
+                    }
+                }
+                None => break,
+            }
+        }
+        self.error_at(offset, message);
+    }
+
+    fn error_at(&mut self, offset: usize, message: String) {
+        let before = &self.text[..offset.min(self.text.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rsplit('\n')
+            .next()
+            .map(|l| l.chars().count())
+            .unwrap_or_default()
+            + 1;
+        self.errors.push(ConfigError {
+            message,
+            line,
+            column,
+        });
+    }
+
+    /// Render a template strictly, recording an error when it fails
+    fn template(&mut self, path: &[Segment], template: &str, data: &Value) {
+        let mut hb = Handlebars::new();
+        hb.set_strict_mode(true);
+        let message = match Template::compile(template) {
+            Err(e) => Some(e.reason().to_string()),
+            Ok(_) => hb
+                .render_template(template, data)
+                .err()
+                .map(|e| e.reason().to_string()),
+        };
+        if let Some(message) = message {
+            self.error(path, format!("Invalid template '{template}': {message}"));
+        }
+    }
+
+    fn errors(mut self) -> Vec<ConfigError> {
+        self.errors.sort_by_key(|e| (e.line, e.column));
+        self.errors.dedup();
+        self.errors
+    }
+}
+
+/// Validate a config file without making any network calls.
+/// Returns every error found along with its location in the file.
+pub(crate) fn validate(text: &str) -> Vec<ConfigError> {
+    let document = match ImDocument::parse(text) {
+        Ok(document) => document,
+        Err(e) => {
+            let offset = e.span().map(|span| span.start).unwrap_or_default();
+            let mut validator = Validator {
+                text,
+                document: ImDocument::parse("").expect("empty document"),
+                errors: Vec::new(),
+            };
+            validator.error_at(offset, e.message().to_string());
+            return validator.errors();
+        }
+    };
+    let mut validator = Validator {
+        text,
+        document,
+        errors: Vec::new(),
+    };
+    match toml::from_str::<TuxWranglerConfig>(text) {
+        Ok(config) => config.validate(&mut validator),
+        Err(e) => {
+            let offset = e.span().map(|span| span.start).unwrap_or_default();
+            validator.error_at(offset, e.message().to_string());
+        }
+    }
+    validator.errors()
+}
+
+impl TuxWranglerConfig {
+    fn validate(&self, validator: &mut Validator) {
+        self.validate_references(validator);
+        self.validate_constraints(validator);
+        self.validate_fetch_versions(validator);
+        self.validate_package_managers(validator);
+        self.validate_platforms(validator);
+        self.validate_build_args(validator);
+        self.validate_templates(validator);
+        self.validate_stage_tags(validator);
+    }
+
+    /// Every base and feature used by a build must be defined, along with any versions it lists
+    fn validate_references(&self, validator: &mut Validator) {
+        let bases = declared_versions(self.bases.iter().map(|base| &base.definition));
+        let features = declared_versions(self.features.iter().map(|feature| &feature.definition));
+        let (bases, features) = (&bases, &features);
+
+        for (i, build) in self.builds.iter().enumerate() {
+            let entries = build
+                .bases
+                .iter()
+                .enumerate()
+                .map(|(j, bd)| ("base", bases, bd, Vec::from(path!["build", i, "bases", j])))
+                .chain(build.features.iter().enumerate().flat_map(|(g, group)| {
+                    group.iter().enumerate().map(move |(j, bd)| {
+                        (
+                            "feature",
+                            features,
+                            bd,
+                            Vec::from(path!["build", i, "features", g, j]),
+                        )
+                    })
+                }));
+            for (kind, defined, bd, path) in entries {
+                let name = bd.name();
+                let Some(versions) = defined.get(name.as_str()) else {
+                    validator.error(&path, format!("Build uses undefined {kind} '{name}'"));
+                    continue;
+                };
+                if let BuildDefinition::Versioned(versioned) = bd {
+                    for (k, version) in versioned.versions.iter().enumerate() {
+                        let path = [path.clone(), Vec::from(path!["versions", k])].concat();
+                        if is_constraint(version) {
+                            // Constraints select from the declared versions once they are resolved
+                            if let Err(e) = Constraint::parse(version) {
+                                validator.error(&path, e.to_string());
+                            }
+                        } else if !versions.iter().any(|versions| versions.contains(version)) {
+                            validator.error(
+                                &path,
+                                format!("Version '{version}' is not declared for {kind} '{name}'"),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every base and feature definition, with its kind and index
+    fn definitions(&self) -> impl Iterator<Item = (usize, (&'static str, &VersionedDefinition))> {
+        self.bases
+            .iter()
+            .map(|base| ("base", &base.definition))
+            .enumerate()
+            .chain(
+                self.features
+                    .iter()
+                    .map(|feature| ("feature", &feature.definition))
+                    .enumerate(),
+            )
+    }
+
+    /// Version constraints must parse and can only be resolved with a `fetch-version`
+    fn validate_constraints(&self, validator: &mut Validator) {
+        for (i, (kind, definition)) in self.definitions() {
+            for (k, version) in definition.versioned.versions.iter().enumerate() {
+                if !is_constraint(version) {
+                    continue;
+                }
+                let path = path![kind, i, "versions", k];
+                if let Err(e) = Constraint::parse(version) {
+                    validator.error(&path, e.to_string());
+                } else if definition.fetch_version.is_none() {
+                    validator.error(
+                        &path,
+                        format!(
+                            "Version constraint '{version}' for {kind} '{}' requires a `fetch-version`",
+                            definition.versioned.name
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Every regex and JSONPath used to fetch versions must compile
+    fn validate_fetch_versions(&self, validator: &mut Validator) {
+        for (i, (kind, definition)) in self.definitions() {
+            let Some(fetch_version) = &definition.fetch_version else {
+                continue;
+            };
+            if let Err(e) = fetch_version.filter().check() {
+                validator.error(&path![kind, i, "fetch-version"], format!("{e:#}"));
+            }
+            match fetch_version {
+                FetchVersion::Http(http) => {
+                    if let Err(e) = JsonPath::parse(&http.json_path) {
+                        validator.error(
+                            &path![kind, i, "fetch-version", "json-path"],
+                            format!("Invalid JSONPath '{}': {e}", http.json_path),
+                        );
+                    }
+                }
+                FetchVersion::Github(github) => {
+                    if let Some(host) = &github.host {
+                        if !Url::parse(host).is_ok_and(|url| url.scheme().starts_with("http")) {
+                            validator.error(
+                                &path![kind, i, "fetch-version", "host"],
+                                format!("Invalid GitHub API URL '{host}'"),
+                            );
+                        }
+                    }
+                }
+                FetchVersion::Git(git) if matches!(git.version_from, VersionFrom::Release) => {
+                    validator.error(
+                        &path![kind, i, "fetch-version", "version-from"],
+                        "Releases are only available from GitHub, use `tag` or `branch`"
+                            .to_string(),
+                    );
+                }
+                FetchVersion::Command(command) => {
+                    if let Some(Err(e)) = command.regex.as_ref().map(|regex| Regex::new(regex)) {
+                        validator.error(
+                            &path![kind, i, "fetch-version", "regex"],
+                            format!("Invalid regex: {e}"),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Every rpm step must have a method for the package manager of each base it is built on
+    fn validate_package_managers(&self, validator: &mut Validator) {
+        let mut missing = Vec::new();
+        for build in &self.builds {
+            let package_managers = build
+                .bases
+                .iter()
+                .flat_map(|bd| {
+                    let name = bd.name();
+                    self.bases
+                        .iter()
+                        .filter(move |base| base.definition.versioned.name == name)
+                })
+                .map(|base| (&base.package_manager, &base.definition.versioned.name))
+                .unique_by(|(package_manager, _)| *package_manager)
+                .collect::<Vec<_>>();
+            let used_features = build
+                .features
+                .iter()
+                .flatten()
+                .map(|bd| bd.name())
+                .collect::<Vec<_>>();
+            for (f, feature) in self.features.iter().enumerate() {
+                if !used_features.contains(&feature.definition.versioned.name) {
+                    continue;
+                }
+                for (s, step) in feature.steps.iter().enumerate() {
+                    let Installation::Rpm(rpm) = &step.installation else {
+                        continue;
+                    };
+                    for (package_manager, base) in &package_managers {
+                        if !rpm.installation_methods.contains_key(*package_manager) {
+                            missing.push((
+                                (f, s, package_manager.to_string()),
+                                format!(
+                                    "Step {s} of feature '{}' has no rpm method for package manager '{package_manager}' used by base '{base}'",
+                                    feature.definition.versioned.name
+                                ),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        for ((f, s, _), message) in missing.into_iter().unique_by(|(key, _)| key.clone()) {
+            validator.error(&path!["feature", f, "step", s], message);
+        }
+    }
+
+    /// Platforms must be `os/architecture[/variant]` and builds can only use platforms of their bases
+    fn validate_platforms(&self, validator: &mut Validator) {
+        for (i, base) in self.bases.iter().enumerate() {
+            for (k, platform) in base.platforms.iter().enumerate() {
+                if let Err(e) = Platform::parse(platform) {
+                    validator.error(&path!["base", i, "platforms", k], e.to_string());
+                }
+            }
+        }
+        for (i, build) in self.builds.iter().enumerate() {
+            for (k, platform) in build.platforms.iter().enumerate() {
+                let path = path!["build", i, "platforms", k];
+                if let Err(e) = Platform::parse(platform) {
+                    validator.error(&path, e.to_string());
+                    continue;
+                }
+                let names = build.bases.iter().map(|bd| bd.name()).collect::<Vec<_>>();
+                for base in self.bases.iter().filter(|base| {
+                    names.contains(&base.definition.versioned.name)
+                        && !base.platforms.is_empty()
+                        && !base.platforms.contains(platform)
+                }) {
+                    validator.error(
+                        &path,
+                        format!(
+                            "Platform '{platform}' is not one of the platforms of base '{}': {}",
+                            base.definition.versioned.name,
+                            base.platforms.join(", ")
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Build arguments and secrets must have valid names and steps can only use defined ones
+    fn validate_build_args(&self, validator: &mut Validator) {
+        let arg_name = Regex::new("^[A-Za-z_][A-Za-z0-9_]*$").expect("regex");
+        for name in self.build_args.keys() {
+            if !arg_name.is_match(name) {
+                validator.error(
+                    &path!["build-args", name.as_str()],
+                    format!("Invalid build argument name '{name}'"),
+                );
+            }
+        }
+        for name in self.secrets.keys() {
+            if name.is_empty() || name.contains([',', '=', ' ']) {
+                validator.error(
+                    &path!["secrets", name.as_str()],
+                    format!("Invalid secret name '{name}'"),
+                );
+            }
+        }
+        for (f, feature) in self.features.iter().enumerate() {
+            for (s, step) in feature.steps.iter().enumerate() {
+                for (k, name) in step.build_args.iter().enumerate() {
+                    if !self.build_args.contains_key(name) {
+                        validator.error(
+                            &path!["feature", f, "step", s, "build-args", k],
+                            format!("Build argument '{name}' is not defined in `[build-args]`"),
+                        );
+                    }
+                }
+                for (k, name) in step.secrets.iter().enumerate() {
+                    if !self.secrets.contains_key(name) {
+                        validator.error(
+                            &path!["feature", f, "step", s, "secrets", k],
+                            format!("Secret '{name}' is not defined in `[secrets]`"),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every template must render in strict mode
+    fn validate_templates(&self, validator: &mut Validator) {
+        let sample = handlebars_data(SAMPLE_VERSION);
+        for (i, base) in self.bases.iter().enumerate() {
+            validator.template(&path!["base", i, "image"], &base.image, &sample);
+            base.definition.validate_templates(validator, "base", i);
+        }
+        let assets = Regex::new(r"release\.assets\.(?:\[([^\]]*)\]|([^\s.}]+))").expect("regex");
+        for (i, feature) in self.features.iter().enumerate() {
+            feature
+                .definition
+                .validate_templates(validator, "feature", i);
+            let sample = match &feature.definition.fetch_version {
+                Some(FetchVersion::Github(github))
+                    if matches!(github.version_from, VersionFrom::Release) =>
+                {
+                    let mut sample = sample.clone();
+                    sample["release"] = sample_release(SAMPLE_VERSION);
+                    // Asset names are only known once the release has been fetched,
+                    // so every asset the steps refer to is assumed to exist
+                    let templates =
+                        feature
+                            .steps
+                            .iter()
+                            .flat_map(|step| match &step.installation {
+                                Installation::Docker(docker) => docker
+                                    .commands
+                                    .iter()
+                                    .chain(&docker.dependencies)
+                                    .collect_vec(),
+                                Installation::Rpm(rpm) => rpm
+                                    .installation_methods
+                                    .values()
+                                    .flat_map(|method| &method.script)
+                                    .collect_vec(),
+                            });
+                    for captures in templates.flat_map(|template| assets.captures_iter(template)) {
+                        if let Some(name) = captures.get(1).or_else(|| captures.get(2)) {
+                            sample["release"]["assets"][name.as_str()] = Value::from("");
+                        }
+                    }
+                    sample
+                }
+                _ => sample.clone(),
+            };
+            for (s, step) in feature.steps.iter().enumerate() {
+                match &step.installation {
+                    Installation::Docker(docker) => {
+                        for (k, command) in docker.commands.iter().enumerate() {
+                            validator.template(
+                                &path!["feature", i, "step", s, "commands", k],
+                                command,
+                                &sample,
+                            );
+                        }
+                    }
+                    Installation::Rpm(rpm) => {
+                        for (package_manager, method) in &rpm.installation_methods {
+                            for (k, line) in method.script.iter().enumerate() {
+                                validator.template(
+                                    &path![
+                                        "feature",
+                                        i,
+                                        "step",
+                                        s,
+                                        package_manager.as_str(),
+                                        "script",
+                                        k
+                                    ],
+                                    line,
+                                    &sample,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Name templates only have access to the versions of the build they belong to
+        let sample = |name: &String| SingleVersioned {
+            name: name.clone(),
+            version: SAMPLE_VERSION.to_string(),
+        };
+        for (i, build) in self.builds.iter().enumerate() {
+            let bases = build.bases.iter().map(|bd| bd.name()).unique();
+            let feature_groups = build
+                .features
+                .iter()
+                .map(|group| group.iter().map(|bd| bd.name()).unique().collect_vec())
+                .multi_cartesian_product()
+                .collect_vec();
+            let feature_groups = if build.features.is_empty() {
+                vec![Vec::new()]
+            } else {
+                feature_groups
+            };
+            let templates = [
+                (
+                    Vec::from(path!["build", i, "image-name"]),
+                    &build.image_name,
+                ),
+                (Vec::from(path!["build", i, "image-tag"]), &build.image_tag),
+            ]
+            .into_iter()
+            .chain(
+                build
+                    .alias_tags
+                    .iter()
+                    .enumerate()
+                    .map(|(k, tag)| (Vec::from(path!["build", i, "alias-tags", k]), tag)),
+            )
+            .chain(build.labels.iter().map(|(name, label)| {
+                (Vec::from(path!["build", i, "labels", name.as_str()]), label)
+            }))
+            .collect_vec();
+            'templates: for (path, template) in templates {
+                for base in bases.clone() {
+                    for features in &feature_groups {
+                        let data = name_template_data(
+                            &sample(&base),
+                            &features.iter().map(sample).collect_vec(),
+                        );
+                        let errors = validator.errors.len();
+                        validator.template(&path, template, &data);
+                        if validator.errors.len() > errors {
+                            continue 'templates;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every Dockerfile stage must have a unique tag, otherwise builds silently share a stage
+    fn validate_stage_tags(&self, validator: &mut Validator) {
+        let hb = Handlebars::new();
+        // Invalid templates are reported by `validate_templates`
+        let render = |template: &String, version: &str| {
+            hb.render_template(template, &handlebars_data(version))
+                .unwrap_or_default()
+        };
+
+        let mut base_tags = HashMap::<String, SingleVersioned>::new();
+        let mut base_tag = HashMap::<SingleVersioned, String>::new();
+        for (i, base) in self.bases.iter().enumerate() {
+            let definition = &base.definition;
+            for version in &definition.versioned.versions {
+                let single_versioned = SingleVersioned {
+                    name: definition.versioned.name.clone(),
+                    version: version.clone(),
+                };
+                let tag = definition
+                    .version_tag
+                    .as_ref()
+                    .map(|tag| render(tag, version))
+                    .unwrap_or_else(|| "temp".to_string());
+                match base_tags.get(&tag) {
+                    Some(other) if other != &single_versioned => validator.error(
+                        &path!["base", i, "version-tag"],
+                        format!(
+                            "Stage tag '{tag}' of base '{}' version '{version}' is also used by base '{}' version '{}'",
+                            single_versioned.name, other.name, other.version
+                        ),
+                    ),
+                    _ => {
+                        base_tags.insert(tag.clone(), single_versioned.clone());
+                    }
+                }
+                base_tag.insert(single_versioned, tag);
+            }
+        }
+
+        let feature_tags = self
+            .features
+            .iter()
+            .flat_map(|feature| {
+                let definition = &feature.definition;
+                definition.versioned.versions.iter().map(|version| {
+                    (
+                        SingleVersioned {
+                            name: definition.versioned.name.clone(),
+                            version: version.clone(),
+                        },
+                        definition
+                            .version_tag
+                            .as_ref()
+                            .map(|tag| render(tag, version))
+                            .unwrap_or_default(),
+                    )
+                })
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut targets = HashMap::<String, (SingleVersioned, Vec<SingleVersioned>)>::new();
+        // Only report each duplicate tag once per build
+        let mut reported = HashSet::new();
+        for (build, base, features) in self.build_combinations(&|_, _| None) {
+            let Some(tag) = base_tag.get(&base) else {
+                continue;
+            };
+            let target = std::iter::once(tag)
+                .chain(features.iter().filter_map(|f| feature_tags.get(f)))
+                .filter(|tag| !tag.is_empty())
+                .join("-");
+            let components = (base, features);
+            match targets.get(&target) {
+                Some(other) if other != &components => {
+                    let i = self
+                        .builds
+                        .iter()
+                        .position(|b| std::ptr::eq(b, build))
+                        .unwrap_or_default();
+                    if !reported.insert((i, target.clone())) {
+                        continue;
+                    }
+                    validator.error(
+                        &path!["build", i],
+                        format!(
+                            "Stage tag '{target}' of build {} is also used by build {}, add a `version-tag` to tell them apart",
+                            describe(&components.0, &components.1),
+                            describe(&other.0, &other.1)
+                        ),
+                    );
+                }
+                _ => {
+                    targets.insert(target, components);
+                }
+            }
+        }
+    }
+}
+
+/// The versions declared for each base or feature name
+fn declared_versions<'a>(
+    definitions: impl Iterator<Item = &'a VersionedDefinition>,
+) -> HashMap<&'a str, Vec<&'a Vec<String>>> {
+    definitions
+        .map(|definition| {
+            (
+                definition.versioned.name.as_str(),
+                &definition.versioned.versions,
+            )
+        })
+        .into_group_map()
+}
+
+fn describe(base: &SingleVersioned, features: &[SingleVersioned]) -> String {
+    format!(
+        "'{}'",
+        std::iter::once(base)
+            .chain(features)
+            .map(|v| format!("{}:{}", v.name, v.version))
+            .join(", ")
+    )
+}
+
+impl VersionedDefinition {
+    fn validate_templates(&self, validator: &mut Validator, kind: &str, i: usize) {
+        if let Some(version_tag) = &self.version_tag {
+            validator.template(
+                &path![kind, i, "version-tag"],
+                version_tag,
+                &handlebars_data(SAMPLE_VERSION),
+            );
+        }
+        // Fetch templates are rendered with the versions from the config
+        let templates = match &self.fetch_version {
+            Some(FetchVersion::Docker(docker)) => vec![(
+                path![kind, i, "fetch-version", "image"].to_vec(),
+                &docker.image,
+            )],
+            Some(FetchVersion::Github(github)) => vec![(
+                path![kind, i, "fetch-version", "project"].to_vec(),
+                &github.project,
+            )],
+            Some(FetchVersion::Registry(registry)) => vec![(
+                path![kind, i, "fetch-version", "image"].to_vec(),
+                &registry.image,
+            )],
+            Some(FetchVersion::Git(git)) => {
+                vec![(path![kind, i, "fetch-version", "url"].to_vec(), &git.url)]
+            }
+            Some(FetchVersion::Http(http)) => {
+                vec![(path![kind, i, "fetch-version", "url"].to_vec(), &http.url)]
+            }
+            Some(FetchVersion::Command(command)) => command
+                .args
+                .iter()
+                .enumerate()
+                .map(|(k, arg)| (path![kind, i, "fetch-version", "args", k].to_vec(), arg))
+                .collect(),
+            None => return,
+        };
+        for (path, template) in templates {
+            for version in &self.versioned.versions {
+                let errors = validator.errors.len();
+                validator.template(&path, template, &handlebars_data(version));
+                if validator.errors.len() > errors {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -1,13 +1,18 @@
-use std::{collections::HashMap, time::SystemTime};
+use std::{cmp::Ordering, collections::HashMap, time::SystemTime};
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use handlebars::Handlebars;
+use itertools::{EitherOrBoth, Itertools};
 use log::debug;
 use regex::Regex;
 use serde_json::{json, Value};
 
-use crate::lock::SingleVersioned;
+use crate::{
+    config::VersionOrder,
+    constraint::{is_constraint, Constraint},
+    lock::SingleVersioned,
+};
 
 pub fn split_version(version: &str) -> Vec<String> {
     let re = Regex::new(r#"[^\w^\*]*([\w\*]*)"#).expect("regex");
@@ -16,21 +21,140 @@ pub fn split_version(version: &str) -> Vec<String> {
         .collect()
 }
 
-pub fn find_tag(target: &str, tags: &[String]) -> Result<String> {
+pub fn find_tag(target: &str, tags: &[String], order: VersionOrder) -> Result<String> {
     debug!("Searching {:?} to match '{}'", tags, target);
+    // Newest first, ties keep the order they were returned in
+    let tags = tags
+        .iter()
+        .sorted_by(|a, b| order.compare(b, a))
+        .collect::<Vec<_>>();
     if target == "latest" {
         return tags
             .first()
-            .cloned()
+            .map(|tag| tag.to_string())
             .context("There were no tags found even though 'latest' version was requested.");
     }
-    tags.iter()
+    tags.into_iter()
         .find(|tag| version_match(target, tag))
         .cloned()
         .context(format!("No matching tags for {target}"))
 }
 
+impl VersionOrder {
+    /// Compare two versions, where the newer version is greater
+    pub(crate) fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            VersionOrder::Numeric => compare_numeric(a, b),
+            VersionOrder::Semver => Semver::parse(a).cmp(&Semver::parse(b)),
+            VersionOrder::Date => date(a).cmp(&date(b)).then_with(|| compare_numeric(a, b)),
+            VersionOrder::Api => Ordering::Equal,
+        }
+    }
+}
+
+/// Compare two segments, numbers are newer than words
+fn compare_segment(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Greater,
+        (Err(_), Ok(_)) => Ordering::Less,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+pub(crate) fn compare_numeric(a: &str, b: &str) -> Ordering {
+    let segments = |version| {
+        split_version(version)
+            .into_iter()
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>()
+    };
+    segments(a)
+        .iter()
+        .zip_longest(segments(b).iter())
+        .map(|pair| match pair {
+            EitherOrBoth::Both(a, b) => compare_segment(a, b),
+            // `21.0.1` is newer than `21.0`, but `21.0-beta` is older
+            EitherOrBoth::Left(a) if a.parse::<u64>().is_ok() => Ordering::Greater,
+            EitherOrBoth::Left(_) => Ordering::Less,
+            EitherOrBoth::Right(b) if b.parse::<u64>().is_ok() => Ordering::Less,
+            EitherOrBoth::Right(_) => Ordering::Greater,
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// The date contained in a version, as (year, month, day)
+fn date(version: &str) -> Option<(u32, u32, u32)> {
+    let re = Regex::new(r"(\d{4})[-._/]?(\d{2})[-._/]?(\d{2})").expect("regex");
+    let captures = re.captures(version)?;
+    let part = |i| captures.get(i)?.as_str().parse().ok();
+    Some((part(1)?, part(2)?, part(3)?))
+}
+
+/// A semantic version, versions that can't be parsed are older than any that can
+#[derive(PartialEq, Eq)]
+struct Semver<'a> {
+    core: Vec<u64>,
+    prerelease: Option<&'a str>,
+}
+
+impl<'a> Semver<'a> {
+    fn parse(version: &'a str) -> Option<Self> {
+        let version = version.trim_start_matches('v');
+        let version = version.split('+').next().unwrap_or_default();
+        let (core, prerelease) = match version.split_once('-') {
+            Some((core, prerelease)) => (core, Some(prerelease)),
+            None => (version, None),
+        };
+        Some(Self {
+            core: core
+                .split('.')
+                .map(|segment| segment.parse().ok())
+                .collect::<Option<_>>()?,
+            prerelease,
+        })
+    }
+}
+
+impl PartialOrd for Semver<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Semver<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.core
+            .cmp(&other.core)
+            .then_with(|| match (self.prerelease, other.prerelease) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a
+                    .split('.')
+                    .zip_longest(b.split('.'))
+                    .map(|pair| match pair {
+                        // numeric identifiers have lower precedence than alphanumeric ones
+                        EitherOrBoth::Both(a, b) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                            (Ok(a), Ok(b)) => a.cmp(&b),
+                            (Ok(_), Err(_)) => Ordering::Less,
+                            (Err(_), Ok(_)) => Ordering::Greater,
+                            (Err(_), Err(_)) => a.cmp(b),
+                        },
+                        EitherOrBoth::Left(_) => Ordering::Greater,
+                        EitherOrBoth::Right(_) => Ordering::Less,
+                    })
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal),
+            })
+    }
+}
+
 pub fn version_match(target: &str, source: &str) -> bool {
+    if is_constraint(target) {
+        return Constraint::parse(target).is_ok_and(|constraint| constraint.matches(source));
+    }
     let target_versions = split_version(target);
     let source_versions = split_version(source);
     if source_versions.len() < target_versions.len() {
@@ -42,8 +166,13 @@ pub fn version_match(target: &str, source: &str) -> bool {
         .all(|(i, v)| v == "*" || source_versions[i] == v)
 }
 
-fn handlebars_data(version: &str) -> Value {
-    json!({"version": version, "versions": split_version(version)})
+pub(crate) fn handlebars_data(version: &str) -> Value {
+    // Constraints are templated with the first version they contain
+    let version = match Constraint::parse(version) {
+        Ok(constraint) if is_constraint(version) => constraint.template_version().to_string(),
+        _ => version.to_string(),
+    };
+    json!({"versions": split_version(&version), "version": version})
 }
 
 pub fn populate_template(template: &str, versions: &[String]) -> Result<HashMap<String, String>> {
@@ -61,33 +190,134 @@ pub fn populate_template(template: &str, versions: &[String]) -> Result<HashMap<
         .collect::<Result<HashMap<String, String>>>()
 }
 
+/// Render templates with the given data, e.g. `handlebars_data` with extra values
+pub(crate) fn render_templates(templates: &[String], data: &Value) -> Result<Vec<String>> {
+    let mut hb = Handlebars::new();
+    hb.set_strict_mode(true);
+    templates
+        .iter()
+        .map(|template| {
+            hb.render_template(template, data)
+                .context(format!("Unable to render template '{template}'"))
+        })
+        .collect()
+}
+
 pub fn populate_name_template(
     template: &str,
     base_version: &SingleVersioned,
     feature_versions: &[SingleVersioned],
 ) -> Result<String> {
-    let today = SystemTime::now();
-    let dt: DateTime<Utc> = today.into();
-    let date = dt.format("%y-%m-%d");
     let mut hb = Handlebars::new();
     hb.set_strict_mode(true);
     hb.render_template(
         template,
-        &json!(feature_versions
-            .iter()
-            .chain(vec![base_version].into_iter())
-            .map(|version| (version.name.clone(), handlebars_data(&version.version)))
-            .chain(
-                vec![(
-                    "base".to_string(),
-                    json!({"name": base_version.name, "v": handlebars_data(&base_version.version)})
-                ), ("date".to_string(), json!(date.to_string()))]
-                .into_iter()
-            )
-            .collect::<HashMap<String, Value>>()),
+        &name_template_data(base_version, feature_versions),
     )
     .context(format!(
         "Unable to render template '{template}' for base '{}'",
         base_version.name
     ))
 }
+
+/// The data available to `image-name`, `image-tag` and `alias-tags` templates
+pub(crate) fn name_template_data(
+    base_version: &SingleVersioned,
+    feature_versions: &[SingleVersioned],
+) -> Value {
+    let today = SystemTime::now();
+    let dt: DateTime<Utc> = today.into();
+    let date = dt.format("%y-%m-%d");
+    json!(feature_versions
+        .iter()
+        .chain(vec![base_version].into_iter())
+        .map(|version| (version.name.clone(), handlebars_data(&version.version)))
+        .chain(
+            vec![
+                (
+                    "base".to_string(),
+                    json!({"name": base_version.name, "v": handlebars_data(&base_version.version)})
+                ),
+                ("date".to_string(), json!(date.to_string()))
+            ]
+            .into_iter()
+        )
+        .collect::<HashMap<String, Value>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn numeric_compares_segments_as_numbers() {
+        let order = VersionOrder::Numeric;
+        assert_eq!(order.compare("21.0.10", "21.0.9"), Ordering::Greater);
+        assert_eq!(order.compare("21.0.1", "21.0"), Ordering::Greater);
+        assert_eq!(order.compare("21.0-beta", "21.0"), Ordering::Less);
+        assert_eq!(order.compare("1.2.3", "1.2.3"), Ordering::Equal);
+    }
+
+    #[test]
+    fn semver_orders_prereleases_before_their_release() {
+        let order = VersionOrder::Semver;
+        assert_eq!(order.compare("1.2.3", "1.2.3-rc.1"), Ordering::Greater);
+        assert_eq!(order.compare("1.2.3-rc.2", "1.2.3-rc.1"), Ordering::Greater);
+        assert_eq!(order.compare("1.2.3-rc.1", "1.2.3-beta"), Ordering::Greater);
+        assert_eq!(
+            order.compare("1.2.3-alpha.1", "1.2.3-alpha.beta"),
+            Ordering::Less
+        );
+        assert_eq!(order.compare("v2.0.0", "1.9.9"), Ordering::Greater);
+        assert_eq!(order.compare("1.0.0", "not-a-version"), Ordering::Greater);
+    }
+
+    #[test]
+    fn date_compares_the_contained_date() {
+        let order = VersionOrder::Date;
+        assert_eq!(
+            order.compare("nightly-2024-01-02", "nightly-20231231"),
+            Ordering::Greater
+        );
+        assert_eq!(order.compare("release", "2024.01.01"), Ordering::Less);
+    }
+
+    #[test]
+    fn api_keeps_the_returned_order() {
+        let found = find_tag("latest", &tags(&["1.0", "3.0", "2.0"]), VersionOrder::Api).unwrap();
+        assert_eq!(found, "1.0");
+    }
+
+    #[test]
+    fn find_tag_picks_the_newest_match() {
+        let available = tags(&["21.0.9", "21.0.10", "17.0.12", "21.0.10-beta"]);
+        assert_eq!(
+            find_tag("21", &available, VersionOrder::Numeric).unwrap(),
+            "21.0.10"
+        );
+        assert_eq!(
+            find_tag("latest", &available, VersionOrder::Numeric).unwrap(),
+            "21.0.10"
+        );
+        assert_eq!(
+            find_tag("17.*", &available, VersionOrder::Numeric).unwrap(),
+            "17.0.12"
+        );
+        assert!(find_tag("11", &available, VersionOrder::Numeric).is_err());
+    }
+
+    #[test]
+    fn find_tag_matches_constraints() {
+        let available = tags(&["17.0.4", "17.0.5", "17.0.6", "18.0.1"]);
+        assert_eq!(
+            find_tag(">=17, <18, !=17.0.6", &available, VersionOrder::Numeric).unwrap(),
+            "17.0.5"
+        );
+    }
+}
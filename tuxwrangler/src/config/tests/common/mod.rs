@@ -0,0 +1,251 @@
+//! Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Once,
+    },
+    thread,
+    time::Duration,
+};
+
+use tw_config::{Cache, Clients, TuxWranglerConfigLocked, DEFAULT_TTL};
+
+static CACHE: Once = Once::new();
+static WORKSPACES: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory holding the config and lock file of a test, removed when the test ends
+pub struct Workspace {
+    pub dir: PathBuf,
+}
+
+impl Workspace {
+    pub fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!(
+            "tuxwrangler-{name}-{}-{}",
+            std::process::id(),
+            WORKSPACES.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self { dir }
+    }
+
+    pub fn config(&self) -> PathBuf {
+        self.dir.join("WRANGLER.toml")
+    }
+
+    pub fn lock(&self) -> PathBuf {
+        self.dir.join("WRANGLER.lock")
+    }
+
+    pub fn write_config(&self, config: &str) {
+        fs::write(self.config(), config).unwrap();
+    }
+
+    /// Resolve the config into the lock file and load it
+    pub async fn update(&self, clients: &mut Clients) -> anyhow::Result<TuxWranglerConfigLocked> {
+        self.update_only(clients, &[]).await
+    }
+
+    /// Resolve only the selected entries (`name` or `name:version`) again and load the lock file
+    pub async fn update_only(
+        &self,
+        clients: &mut Clients,
+        only: &[&str],
+    ) -> anyhow::Result<TuxWranglerConfigLocked> {
+        let only = only.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        tw_config::update_lock(clients, self.config(), self.lock(), &only).await?;
+        tw_config::load_lockfile(self.lock())
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Clients using a cache of their own, so that responses cached by other runs are never used
+pub fn clients() -> Clients {
+    CACHE.call_once(|| {
+        let dir = env::temp_dir().join(format!("tuxwrangler-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        env::set_var("XDG_CACHE_HOME", dir);
+    });
+    Clients::new(None, Cache::new(DEFAULT_TTL, true, false)).unwrap()
+}
+
+/// A request received by a `Server`
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub path: String,
+    /// Header names are lowercase
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A local HTTP server answering every request with the JSON body of the first matching path
+pub struct Server {
+    pub address: String,
+    pub requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Server {
+    pub fn new(routes: Vec<(&str, &str)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let routes = routes
+            .into_iter()
+            .map(|(path, body)| (path.to_string(), body.to_string()))
+            .collect::<Vec<_>>();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let path = line
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_string();
+                let mut headers = Vec::new();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    match line.trim_end().split_once(':') {
+                        Some((name, value)) => {
+                            headers.push((name.to_lowercase(), value.trim().to_string()))
+                        }
+                        None => break,
+                    }
+                }
+                let response = match routes.iter().find(|(route, _)| path.starts_with(route)) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    ),
+                    None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .to_string(),
+                };
+                received.lock().unwrap().push(Request { path, headers });
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        Self { address, requests }
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Run a command in a directory, panicking when it fails
+pub fn run(dir: &Path, program: &str, args: &[&str]) -> String {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap_or_else(|e| panic!("Unable to run {program}: {e}"));
+    assert!(
+        output.status.success(),
+        "{program} {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// The media types of the manifests and manifest lists a `LocalRegistry` returns
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, application/vnd.docker.distribution.manifest.list.v2+json, application/vnd.oci.image.manifest.v1+json, application/vnd.docker.distribution.manifest.v2+json";
+
+/// A `registry:2` container, removed when dropped
+pub struct LocalRegistry {
+    container: String,
+    port: u16,
+    /// `localhost:<port>`
+    pub host: String,
+}
+
+impl LocalRegistry {
+    pub fn start() -> Self {
+        let here = env::temp_dir();
+        let container = run(
+            &here,
+            "docker",
+            &["run", "-d", "-p", "127.0.0.1::5000", "registry:2"],
+        );
+        let port = run(&here, "docker", &["port", &container, "5000/tcp"])
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let registry = Self {
+            container,
+            port,
+            host: format!("localhost:{port}"),
+        };
+        // Wait for the registry to accept requests
+        for _ in 0..50 {
+            if registry
+                .get("/v2/")
+                .is_ok_and(|(status, _)| status.contains(" 200 "))
+            {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        registry
+    }
+
+    /// The status line and body of a response from the registry
+    fn get(&self, path: &str) -> std::io::Result<(String, String)> {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port))?;
+        write!(
+            stream,
+            "GET {path} HTTP/1.0\r\nHost: {}\r\nAccept: {MANIFEST_TYPES}\r\n\r\n",
+            self.host
+        )?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+        let status = head.lines().next().unwrap_or_default().to_string();
+        Ok((status, body.to_string()))
+    }
+
+    /// Fetch a manifest from the registry
+    pub fn manifest(&self, repository: &str, reference: &str) -> serde_json::Value {
+        let (status, body) = self
+            .get(&format!("/v2/{repository}/manifests/{reference}"))
+            .unwrap();
+        assert!(status.contains(" 200 "), "{reference}: {status}");
+        serde_json::from_str(&body).unwrap()
+    }
+}
+
+impl Drop for LocalRegistry {
+    fn drop(&mut self) {
+        let _ = Command::new("docker")
+            .args(["rm", "-f", &self.container])
+            .output();
+    }
+}
@@ -0,0 +1,107 @@
+//! Versions from the refs of a bare git repository
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{clients, run, Workspace};
+
+/// A bare repository with a few release tags and branches
+fn repository(workspace: &Workspace) -> String {
+    let dir = &workspace.dir;
+    run(dir, "git", &["init", "--quiet", "--bare", "repo.git"]);
+    run(dir, "git", &["init", "--quiet", "work"]);
+    let work = dir.join("work");
+    let git = |args: &[&str]| {
+        let args = ["-c", "user.name=test", "-c", "user.email=test@example.com"]
+            .iter()
+            .chain(args)
+            .copied()
+            .collect::<Vec<_>>();
+        run(&work, "git", &args)
+    };
+    git(&["commit", "--quiet", "--allow-empty", "-m", "release"]);
+    for tag in ["v1.2.3", "v1.3.0", "v2.0.0-rc1", "not-a-version"] {
+        git(&["tag", tag]);
+    }
+    for branch in ["release-1.4", "release-1.10", "main"] {
+        git(&["branch", branch]);
+    }
+    git(&[
+        "push",
+        "--quiet",
+        "--tags",
+        "../repo.git",
+        "release-1.4",
+        "release-1.10",
+        "main",
+    ]);
+    dir.join("repo.git").display().to_string()
+}
+
+fn config(fetch_version: &str, versions: &str) -> String {
+    format!(
+        r#"
+registry = "example.com"
+
+[[base]]
+name = "ubuntu"
+versions = ["22.04"]
+image = "ubuntu:{{{{version}}}}"
+package-manager = "apt"
+
+[[feature]]
+name = "tool"
+versions = {versions}
+
+[feature.fetch-version]
+type = "git"
+{fetch_version}
+
+[[feature.step]]
+method = "docker"
+commands = ["RUN echo tool"]
+
+[[build]]
+image-name = "tool"
+image-tag = "{{{{tool.version}}}}"
+bases = ["ubuntu"]
+features = [["tool"]]
+"#
+    )
+}
+
+async fn versions(workspace: &Workspace) -> anyhow::Result<BTreeMap<String, String>> {
+    let locked = workspace.update(&mut clients()).await?;
+    Ok(locked
+        .features
+        .into_iter()
+        .map(|f| (f.target_version.unwrap(), f.version))
+        .collect())
+}
+
+#[tokio::test]
+async fn picks_versions_from_tags() {
+    let workspace = Workspace::new("git-tags");
+    let url = repository(&workspace);
+    workspace.write_config(&config(
+        &format!("url = '{url}'\nstrip-prefix = \"v\"\nprerelease = false"),
+        r#"["1.2", "1", "latest"]"#,
+    ));
+    let versions = versions(&workspace).await.unwrap();
+    assert_eq!(versions["1.2"], "1.2.3");
+    assert_eq!(versions["1"], "1.3.0");
+    assert_eq!(versions["latest"], "1.3.0");
+}
+
+#[tokio::test]
+async fn picks_versions_from_branches() {
+    let workspace = Workspace::new("git-branches");
+    let url = repository(&workspace);
+    workspace.write_config(&config(
+        &format!("url = '{url}'\nversion-from = \"branch\"\nstrip-prefix = \"release-\"\ninclude = [\"^release-\"]"),
+        r#"["1"]"#,
+    ));
+    let versions = versions(&workspace).await.unwrap();
+    assert_eq!(versions["1"], "1.10");
+}
@@ -0,0 +1,116 @@
+//! Versions from JSON documents, served by a local HTTP server
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{clients, Server, Workspace};
+
+const CONFIG: &str = r#"
+registry = "example.com"
+
+[[base]]
+name = "ubuntu"
+versions = ["22.04"]
+image = "ubuntu:{{version}}"
+package-manager = "apt"
+
+[[feature]]
+name = "tomcat"
+versions = ["10", "9"]
+
+[feature.fetch-version]
+type = "http"
+url = "http://SERVER/tomcat-{{versions.0}}/releases.json"
+json-path = "$.releases[*].version"
+headers = { Accept = "application/json" }
+
+[[feature.step]]
+method = "docker"
+commands = ["RUN echo tomcat"]
+
+[[feature]]
+name = "internal"
+versions = ["latest"]
+
+[feature.fetch-version]
+type = "http"
+url = "http://SERVER/tomcat-10/releases.json"
+json-path = "$.internal"
+headers = { Authorization = "Bearer secret" }
+
+[[feature.step]]
+method = "docker"
+commands = ["RUN echo internal"]
+
+[[build]]
+image-name = "tomcat"
+image-tag = "{{tomcat.version}}"
+bases = ["ubuntu"]
+features = [["tomcat"], ["internal"]]
+"#;
+
+fn versions(locked: &tw_config::TuxWranglerConfigLocked) -> BTreeMap<String, String> {
+    locked
+        .features
+        .iter()
+        .map(|f| {
+            (
+                format!("{}:{}", f.name, f.target_version.clone().unwrap()),
+                f.version.clone(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn picks_versions_selected_by_the_json_path() {
+    let server = Server::new(vec![
+        (
+            "/tomcat-10/",
+            r#"{"releases": [{"version": "10.1.28"}, {"version": "10.1.30"}, {"version": "10.1.29"}], "internal": ["1.0", "1.2"]}"#,
+        ),
+        (
+            "/tomcat-9/",
+            r#"{"releases": [{"version": "9.0.93"}, {"version": 9.1}]}"#,
+        ),
+    ]);
+    let workspace = Workspace::new("http-source");
+    workspace.write_config(&CONFIG.replace("SERVER", &server.address));
+    let mut clients = clients();
+    let locked = workspace.update(&mut clients).await.unwrap();
+
+    let versions = versions(&locked);
+    assert_eq!(versions["tomcat:10"], "10.1.30");
+    assert_eq!(versions["tomcat:9"], "9.1");
+    assert_eq!(versions["internal:latest"], "1.2");
+}
+
+#[tokio::test]
+async fn requests_with_other_headers_are_not_shared() {
+    let server = Server::new(vec![
+        (
+            "/tomcat-10/",
+            r#"{"releases": [{"version": "10.1.30"}], "internal": "1.2"}"#,
+        ),
+        ("/tomcat-9/", r#"{"releases": [{"version": "9.0.93"}]}"#),
+    ]);
+    let workspace = Workspace::new("http-headers");
+    workspace.write_config(&CONFIG.replace("SERVER", &server.address));
+    let mut clients = clients();
+    workspace.update(&mut clients).await.unwrap();
+
+    let requests = server.requests();
+    let tomcat_10 = requests
+        .iter()
+        .filter(|request| request.path == "/tomcat-10/releases.json")
+        .collect::<Vec<_>>();
+    assert_eq!(tomcat_10.len(), 2, "{requests:?}");
+    assert!(tomcat_10.iter().any(
+        |request| request.header("accept") == Some("application/json")
+            && request.header("authorization").is_none()
+    ));
+    assert!(tomcat_10
+        .iter()
+        .any(|request| request.header("authorization") == Some("Bearer secret")));
+}
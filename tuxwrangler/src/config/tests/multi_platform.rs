@@ -0,0 +1,90 @@
+//! Building and pushing images for several platforms, needs Docker with QEMU emulation and a local `registry:2`
+
+mod common;
+
+use common::{clients, LocalRegistry, Workspace};
+use tw_config::{lock::ImageIdentifier, BuildOptions};
+
+#[tokio::test]
+#[ignore = "needs a Docker daemon with QEMU emulation"]
+async fn builds_and_pushes_every_platform() {
+    let registry = LocalRegistry::start();
+    let workspace = Workspace::new("multi-platform");
+    workspace.write_config(&format!(
+        r#"
+registry = "{}"
+
+[[base]]
+name = "alpine"
+versions = ["3.20"]
+image = "alpine:{{{{version}}}}"
+package-manager = "apk"
+version-tag = "alpine-{{{{version}}}}"
+platforms = ["linux/amd64", "linux/arm64"]
+
+[[feature]]
+name = "hello"
+versions = ["1"]
+version-tag = "hello-{{{{version}}}}"
+
+[[feature.step]]
+method = "docker"
+commands = ["RUN uname -m > /arch"]
+
+[[build]]
+image-name = "tuxwrangler/multi"
+image-tag = "{{{{hello.version}}}}-alpine{{{{alpine.version}}}}"
+bases = ["alpine"]
+features = [["hello"]]
+"#,
+        registry.host
+    ));
+    let mut clients = clients();
+    let locked = workspace.update(&mut clients).await.unwrap();
+
+    // Each platform is built from the image of its own platform
+    let base = &locked.bases[0];
+    let ImageIdentifier::Digest { digest } = &base.identifier else {
+        panic!("alpine was locked by tag");
+    };
+    assert_eq!(
+        base.platforms.keys().collect::<Vec<_>>(),
+        ["linux/amd64", "linux/arm64"]
+    );
+    assert!(base
+        .platforms
+        .values()
+        .all(|d| d.starts_with("sha256:") && d != digest));
+    assert_eq!(locked.builds[0].platforms, ["linux/amd64", "linux/arm64"]);
+
+    tw_config::build_images(
+        &mut clients,
+        locked,
+        &BuildOptions {
+            push: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let list = registry.manifest("tuxwrangler/multi", "1-alpine3.20");
+    let platforms = list["manifests"]
+        .as_array()
+        .unwrap_or_else(|| panic!("not a manifest list: {list}"))
+        .iter()
+        .map(|m| {
+            format!(
+                "{}/{}",
+                m["platform"]["os"].as_str().unwrap(),
+                m["platform"]["architecture"].as_str().unwrap()
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(platforms, ["linux/amd64", "linux/arm64"]);
+    for platform in ["linux-amd64", "linux-arm64"] {
+        let tag = format!("1-alpine3.20-{platform}");
+        let manifest = registry.manifest("tuxwrangler/multi", &tag);
+        assert_eq!(manifest["schemaVersion"], 2, "{tag}: {manifest}");
+    }
+}
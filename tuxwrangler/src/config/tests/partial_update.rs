@@ -0,0 +1,74 @@
+//! Updating only some entries of a lock file with `update --only`
+
+mod common;
+
+use common::{clients, Workspace};
+use tw_config::lock::{Installation, TuxWranglerConfigLocked};
+
+fn config(tool_command: &str) -> String {
+    format!(
+        r#"
+registry = "example.com"
+
+[[base]]
+name = "ubuntu"
+versions = ["22.04"]
+image = "ubuntu:{{{{version}}}}"
+package-manager = "apt"
+
+[[feature]]
+name = "tool"
+versions = ["1.0"]
+
+[[feature.step]]
+method = "docker"
+commands = ["{tool_command}"]
+
+[[feature]]
+name = "other"
+versions = ["2.0"]
+
+[[feature.step]]
+method = "docker"
+commands = ["RUN echo other"]
+
+[[build]]
+image-name = "tool"
+image-tag = "{{{{tool.version}}}}"
+bases = ["ubuntu"]
+features = [["tool"], ["other"]]
+"#
+    )
+}
+
+fn commands(locked: &TuxWranglerConfigLocked, name: &str) -> Vec<String> {
+    locked
+        .features
+        .iter()
+        .filter(|feature| feature.name == name)
+        .flat_map(|feature| &feature.steps)
+        .flat_map(|step| match &step.installation {
+            Installation::Docker(docker) => docker.commands.clone(),
+            Installation::Rpm(_) => Vec::new(),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn entries_that_are_not_selected_keep_their_locked_steps() {
+    let workspace = Workspace::new("partial-update");
+    workspace.write_config(&config("RUN echo tool"));
+    let mut clients = clients();
+    workspace.update(&mut clients).await.unwrap();
+
+    workspace.write_config(&config("RUN echo changed"));
+    let locked = workspace
+        .update_only(&mut clients, &["other"])
+        .await
+        .unwrap();
+    assert_eq!(commands(&locked, "tool"), ["RUN echo tool"]);
+    assert_eq!(commands(&locked, "other"), ["RUN echo other"]);
+
+    let locked = workspace.update(&mut clients).await.unwrap();
+    assert_eq!(commands(&locked, "tool"), ["RUN echo changed"]);
+}
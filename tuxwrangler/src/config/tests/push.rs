@@ -0,0 +1,59 @@
+//! Pushing built images to a registry, needs Docker to run a local `registry:2`
+
+mod common;
+
+use common::{clients, LocalRegistry, Workspace};
+use tw_config::BuildOptions;
+
+#[tokio::test]
+#[ignore = "needs a Docker daemon"]
+async fn pushes_every_tag_to_the_registry() {
+    let registry = LocalRegistry::start();
+    let workspace = Workspace::new("push");
+    workspace.write_config(&format!(
+        r#"
+registry = "{}"
+
+[[base]]
+name = "alpine"
+versions = ["3.20"]
+image = "alpine:{{{{version}}}}"
+package-manager = "apk"
+version-tag = "alpine-{{{{version}}}}"
+
+[[feature]]
+name = "hello"
+versions = ["1"]
+version-tag = "hello-{{{{version}}}}"
+
+[[feature.step]]
+method = "docker"
+commands = ["RUN echo hello > /hello"]
+
+[[build]]
+image-name = "tuxwrangler/hello"
+image-tag = "{{{{hello.version}}}}-alpine{{{{alpine.version}}}}"
+alias-tags = ["latest"]
+bases = ["alpine"]
+features = [["hello"]]
+"#,
+        registry.host
+    ));
+    let mut clients = clients();
+    let locked = workspace.update(&mut clients).await.unwrap();
+    tw_config::build_images(
+        &mut clients,
+        locked,
+        &BuildOptions {
+            push: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    for tag in ["1-alpine3.20", "latest"] {
+        let manifest = registry.manifest("tuxwrangler/hello", tag);
+        assert_eq!(manifest["schemaVersion"], 2, "{tag}: {manifest}");
+    }
+}
@@ -0,0 +1,59 @@
+//! Versions from the tag list of a registry, served by a local HTTP server
+
+mod common;
+
+use common::{clients, Server, Workspace};
+
+#[tokio::test]
+async fn picks_versions_from_the_tag_list() {
+    let server = Server::new(vec![(
+        "/v2/library/temurin/tags/list",
+        r#"{"name": "library/temurin", "tags": ["17.0.11_9-jdk-jammy", "17.0.12_7-jdk-jammy", "17.0.12_7-jre-jammy", "21.0.4_7-jdk-jammy", "22-ea-jdk-jammy", "latest"]}"#,
+    )]);
+    let workspace = Workspace::new("registry-source");
+    workspace.write_config(&format!(
+        r#"
+registry = "example.com"
+
+[[base]]
+name = "ubuntu"
+versions = ["22.04"]
+image = "ubuntu:{{{{version}}}}"
+package-manager = "apt"
+
+[[feature]]
+name = "temurin"
+versions = ["17", "21", "latest"]
+
+[feature.fetch-version]
+type = "registry"
+image = "localhost:{}/library/temurin"
+capture = '^(?P<version>[\d.]+)(_\d+)?-jdk-jammy$'
+prerelease = false
+
+[[feature.step]]
+method = "docker"
+commands = ["RUN echo temurin"]
+
+[[build]]
+image-name = "java"
+image-tag = "{{{{temurin.version}}}}"
+bases = ["ubuntu"]
+features = [["temurin"]]
+"#,
+        server.address.split(':').nth(1).unwrap()
+    ));
+    let mut clients = clients();
+    let locked = workspace.update(&mut clients).await.unwrap();
+
+    let versions = locked
+        .features
+        .iter()
+        .map(|f| (f.target_version.clone().unwrap(), f.version.clone()))
+        .collect::<std::collections::BTreeMap<_, _>>();
+    assert_eq!(versions["17"], "17.0.12");
+    assert_eq!(versions["21"], "21.0.4");
+    assert_eq!(versions["latest"], "21.0.4");
+    // Each repository is only listed once per update
+    assert_eq!(server.requests().len(), 1);
+}
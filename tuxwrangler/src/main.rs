@@ -1,12 +1,18 @@
 use std::{
+    fs,
     path::{Path, PathBuf},
     process::exit,
+    time::Duration,
 };
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use log::{error, info};
 use serde_json::json;
-use tw_config::{build_images, load_lockfile, update_lock, write_dockerfile, Clients};
+use tw_config::{
+    build_images, check_lock, default_jobs, diff::Changes, load_lockfile, lock_changes,
+    push_images, update_lock, validate_config, write_dockerfile, BuildFilter, BuildOptions, Cache,
+    Clients, DEFAULT_TTL,
+};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
@@ -18,6 +24,14 @@ struct Args {
     #[clap(long = "github-token", env = "GITHUB_TOKEN")]
     github_token: Option<String>,
 
+    /// Fetch versions and digests again instead of using cached responses
+    #[clap(long, global = true)]
+    refresh: bool,
+
+    /// How long responses of version sources are cached, in seconds
+    #[clap(long = "cache-ttl", global = true, default_value_t = DEFAULT_TTL.as_secs())]
+    cache_ttl: u64,
+
     #[clap(subcommand)]
     command: Command,
 }
@@ -25,9 +39,15 @@ struct Args {
 #[derive(Parser, Debug)]
 enum Command {
     Build(BuildArgs),
+    Push(PushArgs),
     Update(UpdateArgs),
     Write(WriteArgs),
     Images(ImagesArgs),
+    Diff(DiffArgs),
+    Changelog(ChangelogArgs),
+    Check(CheckArgs),
+    Validate(ValidateArgs),
+    Cache(CacheArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -35,8 +55,44 @@ struct BuildArgs {
     #[clap(long, short)]
     #[arg( default_value = default_config("lock").into_os_string())]
     lock: PathBuf,
+    #[clap(flatten)]
+    filter: FilterArgs,
+    /// Do not tag the images with their `image_name:image_tag` and alias tags
     #[clap(long = "skip-tags")]
     skip_tags: bool,
+    /// Push the images to the configured registry once they are built
+    #[clap(long)]
+    push: bool,
+    /// The maximum number of builds to run at once (defaults to the number of CPUs)
+    #[clap(long, short)]
+    jobs: Option<usize>,
+    /// Keep building the remaining images after a build fails
+    #[clap(long = "keep-going")]
+    keep_going: bool,
+    /// Import the build cache (`type=registry,ref=<image>` or `type=local,src=<dir>`), `{target}` is replaced by the build target and its platform (`<target>-linux-arm64`) when there are several
+    #[clap(long = "cache-from")]
+    cache_from: Vec<String>,
+    /// Export the build cache (`type=registry,ref=<image>,mode=max` or `type=local,dest=<dir>,mode=max`), `{target}` is replaced by the build target and its platform (`<target>-linux-arm64`) when there are several
+    #[clap(long = "cache-to")]
+    cache_to: Vec<String>,
+    /// Build every step again instead of using cached layers
+    #[clap(long = "no-cache")]
+    no_cache: bool,
+    /// Use the local base images instead of pulling newer versions of their tags
+    #[clap(long = "no-pull")]
+    no_pull: bool,
+}
+
+#[derive(Parser, Debug)]
+struct PushArgs {
+    #[clap(long, short)]
+    #[arg( default_value = default_config("lock").into_os_string())]
+    lock: PathBuf,
+    #[clap(flatten)]
+    filter: FilterArgs,
+    /// The maximum number of images to push at once (defaults to the number of CPUs)
+    #[clap(long, short)]
+    jobs: Option<usize>,
 }
 
 #[derive(Parser, Debug)]
@@ -44,6 +100,8 @@ struct WriteArgs {
     #[clap(long, short)]
     #[arg( default_value = default_config("lock").into_os_string())]
     lock: PathBuf,
+    #[clap(flatten)]
+    filter: FilterArgs,
     #[clap( default_value = default_dir().into_os_string(), long = "out")]
     out_dir: PathBuf,
 }
@@ -53,6 +111,107 @@ struct ImagesArgs {
     #[clap(long, short)]
     #[arg( default_value = default_config("lock").into_os_string())]
     lock: PathBuf,
+    #[clap(flatten)]
+    filter: FilterArgs,
+}
+
+/// Select a subset of the builds in the lock file
+#[derive(Parser, Debug)]
+struct FilterArgs {
+    /// Only include builds whose target matches the glob
+    #[clap(long = "target")]
+    targets: Vec<String>,
+    /// Only include builds on the base (`name` or `name=<version glob>`)
+    #[clap(long = "base")]
+    bases: Vec<String>,
+    /// Only include builds with the feature (`name` or `name=<version glob>`)
+    #[clap(long = "feature")]
+    features: Vec<String>,
+    /// Only include builds whose image name matches the glob
+    #[clap(long = "image-name")]
+    image_names: Vec<String>,
+    /// Only include builds with an image tag matching the glob
+    #[clap(long = "image-tag")]
+    image_tags: Vec<String>,
+}
+
+impl From<FilterArgs> for BuildFilter {
+    fn from(args: FilterArgs) -> Self {
+        Self {
+            targets: args.targets,
+            bases: args.bases,
+            features: args.features,
+            image_names: args.image_names,
+            image_tags: args.image_tags,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct DiffArgs {
+    /// The lock file before the changes
+    #[clap(long)]
+    old: PathBuf,
+    /// The lock file after the changes
+    #[clap(long)]
+    #[arg( default_value = default_config("lock").into_os_string())]
+    new: PathBuf,
+    #[clap(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+#[derive(Parser, Debug)]
+struct ChangelogArgs {
+    /// The lock file before the update
+    #[clap(long)]
+    old: PathBuf,
+    /// The lock file after the update
+    #[clap(long)]
+    #[arg( default_value = default_config("lock").into_os_string())]
+    new: PathBuf,
+    /// Write the changelog to a file instead of stdout
+    #[clap(long)]
+    out: Option<PathBuf>,
+}
+
+/// Check that the lock file is up to date with the config
+#[derive(Parser, Debug)]
+struct CheckArgs {
+    #[clap(long, short)]
+    #[arg( default_value = default_config("toml").into_os_string())]
+    config: PathBuf,
+    #[clap(long, short)]
+    #[arg( default_value = default_config("lock").into_os_string())]
+    lock: PathBuf,
+}
+
+/// Validate the config without making any network calls
+#[derive(Parser, Debug)]
+struct ValidateArgs {
+    #[clap(long, short)]
+    #[arg( default_value = default_config("toml").into_os_string())]
+    config: PathBuf,
+}
+
+/// Inspect the cache of version source responses
+#[derive(Parser, Debug)]
+struct CacheArgs {
+    #[clap(subcommand)]
+    command: CacheCommand,
+}
+
+#[derive(Parser, Debug)]
+enum CacheCommand {
+    /// List the cached responses
+    Ls,
+    /// Remove every cached response
+    Clear,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum Format {
+    Text,
+    Json,
 }
 
 #[derive(Parser, Debug)]
@@ -63,6 +222,13 @@ struct UpdateArgs {
     #[clap(long, short)]
     #[arg( default_value = default_config("lock").into_os_string())]
     lock: PathBuf,
+    /// Only resolve the given base or feature (`name` or `name:version`) again,
+    /// keeping everything else as it is in the existing lock file
+    #[clap(long)]
+    only: Vec<String>,
+    /// Resolve versions from the cache and the existing lock file without any network calls
+    #[clap(long)]
+    offline: bool,
 }
 
 #[tokio::main]
@@ -93,7 +259,9 @@ async fn main() {
             },
         )
         .init();
-    let mut clients = match Clients::new(args.github_token) {
+    let offline = matches!(&args.command, Command::Update(update_args) if update_args.offline);
+    let cache = Cache::new(Duration::from_secs(args.cache_ttl), args.refresh, offline);
+    let mut clients = match Clients::new(args.github_token, cache.clone()) {
         Ok(c) => c,
         Err(e) => {
             error!("Unable to create an TuxWrangler client:\n{:?}", e);
@@ -103,14 +271,27 @@ async fn main() {
 
     match args.command {
         Command::Build(build_args) => {
-            let locked = match load_lockfile(build_args.lock) {
+            let locked = match load_lockfile(build_args.lock)
+                .and_then(|locked| locked.filter_builds(&build_args.filter.into()))
+            {
                 Ok(locked) => locked,
                 Err(e) => {
                     error!("Unable to load lock file:\n{:?}", e);
                     exit(1)
                 }
             };
-            match build_images(&clients, locked, build_args.skip_tags).await {
+            let defaults = BuildOptions::default();
+            let options = BuildOptions {
+                skip_tags: build_args.skip_tags,
+                push: build_args.push,
+                jobs: build_args.jobs.unwrap_or(defaults.jobs),
+                keep_going: build_args.keep_going,
+                cache_from: build_args.cache_from,
+                cache_to: build_args.cache_to,
+                no_cache: build_args.no_cache,
+                pull: !build_args.no_pull,
+            };
+            match build_images(&mut clients, locked, &options).await {
                 Ok(_) => info!("Images build successfully"),
                 Err(e) => {
                     error!("Unable to build images:\n{:?}", e);
@@ -118,8 +299,34 @@ async fn main() {
                 }
             }
         }
+        Command::Push(push_args) => {
+            let locked = match load_lockfile(push_args.lock)
+                .and_then(|locked| locked.filter_builds(&push_args.filter.into()))
+            {
+                Ok(locked) => locked,
+                Err(e) => {
+                    error!("Unable to load lock file:\n{:?}", e);
+                    exit(1)
+                }
+            };
+            let jobs = push_args.jobs.unwrap_or_else(default_jobs);
+            match push_images(&mut clients, locked, jobs).await {
+                Ok(_) => info!("Images pushed successfully"),
+                Err(e) => {
+                    error!("Unable to push images:\n{:?}", e);
+                    exit(1)
+                }
+            }
+        }
         Command::Update(update_args) => {
-            match update_lock(&mut clients, update_args.config, update_args.lock).await {
+            match update_lock(
+                &mut clients,
+                update_args.config,
+                update_args.lock,
+                &update_args.only,
+            )
+            .await
+            {
                 Ok(_) => info!("Lockfile updated successfully"),
                 Err(e) => {
                     error!("Unable to update lockfile:\n{:?}", e);
@@ -128,7 +335,9 @@ async fn main() {
             }
         }
         Command::Write(write_args) => {
-            let locked = match load_lockfile(write_args.lock) {
+            let locked = match load_lockfile(write_args.lock)
+                .and_then(|locked| locked.filter_builds(&write_args.filter.into()))
+            {
                 Ok(locked) => locked,
                 Err(e) => {
                     error!("Unable to load lock file:\n{:?}", e);
@@ -147,7 +356,9 @@ async fn main() {
             }
         }
         Command::Images(image_args) => {
-            let locked = match load_lockfile(image_args.lock) {
+            let locked = match load_lockfile(image_args.lock)
+                .and_then(|locked| locked.filter_builds(&image_args.filter.into()))
+            {
                 Ok(locked) => locked,
                 Err(e) => {
                     error!("Unable to load lock file:\n{:?}", e);
@@ -158,10 +369,107 @@ async fn main() {
                 "images={}", serde_json::to_string(&json!(locked
                     .builds
                     .iter()
-                    .map(|build| json!({"target": &build.target, "image_name": &build.image_name, "image_tag": &build.image_tag}))
+                    .map(|build| json!({"target": &build.target, "image_name": &build.image_name, "image_tag": &build.image_tag, "alias_tags": &build.alias_tags, "platforms": &build.platforms}))
                     .collect::<Vec<_>>())).expect("Images contained invalid json.")
             );
         }
+        Command::Diff(diff_args) => {
+            let changes = match lock_changes(diff_args.old, diff_args.new) {
+                Ok(changes) => changes,
+                Err(e) => {
+                    error!("Unable to compare lock files:\n{:?}", e);
+                    exit(1)
+                }
+            };
+            match diff_args.format {
+                Format::Text if changes.has_changes() => print!("{}", Changes::from(&changes)),
+                Format::Text => println!("No changes"),
+                Format::Json => println!(
+                    "{}",
+                    serde_json::to_string(&changes).expect("Changes contained invalid json.")
+                ),
+            }
+        }
+        Command::Changelog(changelog_args) => {
+            let changelog = match lock_changes(changelog_args.old, changelog_args.new) {
+                Ok(changes) => changes.changelog(),
+                Err(e) => {
+                    error!("Unable to compare lock files:\n{:?}", e);
+                    exit(1)
+                }
+            };
+            match changelog_args.out {
+                Some(out) => match fs::write(&out, changelog) {
+                    Ok(_) => info!("Changelog written to '{}'", out.display()),
+                    Err(e) => {
+                        error!("Unable to write changelog:\n{:?}", e);
+                        exit(1)
+                    }
+                },
+                None => print!("{changelog}"),
+            }
+        }
+        Command::Check(check_args) => match check_lock(check_args.config, check_args.lock) {
+            Ok(reasons) if reasons.is_empty() => info!("Lock file is up to date"),
+            Ok(reasons) => {
+                for reason in reasons {
+                    error!("{reason}");
+                }
+                error!("Lock file is out of date, run `tuxwrangler update`");
+                exit(1)
+            }
+            Err(e) => {
+                error!("Unable to check lock file:\n{:?}", e);
+                exit(1)
+            }
+        },
+        Command::Validate(validate_args) => match validate_config(validate_args.config.clone()) {
+            Ok(errors) if errors.is_empty() => info!("Config is valid"),
+            Ok(errors) => {
+                for e in &errors {
+                    error!("{}:{e}", validate_args.config.display());
+                }
+                error!("Config has {} error(s)", errors.len());
+                exit(1)
+            }
+            Err(e) => {
+                error!("Unable to validate config:\n{:?}", e);
+                exit(1)
+            }
+        },
+        Command::Cache(cache_args) => match cache_args.command {
+            CacheCommand::Ls => match cache.entries() {
+                Ok(entries) if entries.is_empty() => info!(
+                    "No cached responses in '{}'",
+                    cache
+                        .dir()
+                        .map(|dir| dir.display().to_string())
+                        .unwrap_or_default()
+                ),
+                Ok(entries) => {
+                    for entry in entries {
+                        println!(
+                            "{:<10} {:>8}{} {}",
+                            entry.source,
+                            format_age(entry.age),
+                            if entry.expired { " (expired)" } else { "" },
+                            entry.key
+                        );
+                    }
+                }
+                Err(e) => {
+                    error!("Unable to list the cache:\n{:?}", e);
+                    exit(1)
+                }
+            },
+            CacheCommand::Clear => match cache.clear() {
+                Ok(count) => info!("Removed {count} cached response(s)"),
+                Err(e) => {
+                    error!("Unable to clear the cache:\n{:?}", e);
+                    exit(1)
+                }
+            },
+        },
     };
 }
 
@@ -169,6 +477,15 @@ fn default_config(extension: &str) -> PathBuf {
     Path::new("WRANGLER").with_extension(extension)
 }
 
+fn format_age(age: Duration) -> String {
+    let minutes = age.as_secs() / 60;
+    match minutes {
+        0..60 => format!("{minutes}m"),
+        60..1440 => format!("{}h{}m", minutes / 60, minutes % 60),
+        _ => format!("{}d{}h", minutes / 1440, minutes % 1440 / 60),
+    }
+}
+
 fn default_dir() -> PathBuf {
     Path::new("build").to_path_buf()
 }